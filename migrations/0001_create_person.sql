@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS `person` (
+    `id` INT UNSIGNED NOT NULL AUTO_INCREMENT,
+    `name` VARCHAR(255) NOT NULL,
+    `email` VARCHAR(255) NOT NULL,
+    `phone` VARCHAR(32) NOT NULL,
+    `address` VARCHAR(255) NOT NULL,
+    `city` VARCHAR(128) NOT NULL,
+    `state` CHAR(2) NOT NULL,
+    `version` INT UNSIGNED NOT NULL DEFAULT 1,
+    PRIMARY KEY (`id`),
+    KEY `person_email` (`email`)
+)
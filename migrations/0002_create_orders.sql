@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS `orders` (
+    `id` BIGINT UNSIGNED NOT NULL,
+    `reference` VARCHAR(32) NOT NULL,
+    `status` VARCHAR(16) NOT NULL,
+    `quantity` INT NOT NULL,
+    `total` DECIMAL(10, 2) NOT NULL,
+    `coupon` VARCHAR(16) NULL,
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `orders_reference` (`reference`)
+)
@@ -0,0 +1 @@
+ALTER TABLE `person` ADD COLUMN `updated_by` VARCHAR(255) NULL
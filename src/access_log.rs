@@ -0,0 +1,229 @@
+use crate::config::{AccessLogConfig, AccessLogFormat};
+use crate::request::Request;
+use crate::response::Response;
+use serde_json::json;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// What one access log line records about a request.
+pub struct AccessEntry<'a> {
+    pub time: SystemTime,
+    pub peer_addr: Option<IpAddr>,
+    /// `None` when the request could not be parsed.
+    pub request: Option<&'a Request>,
+    pub status: u16,
+    pub bytes: usize,
+    pub latency: Duration,
+}
+
+/// Writes `AccessEntry` lines in the configured format to stdout or a file
+/// that is rotated once it reaches `max_bytes`.
+pub struct AccessLog {
+    config: AccessLogConfig,
+    file: Mutex<Option<LogFile>>,
+}
+
+struct LogFile {
+    file: File,
+    size: u64,
+}
+
+fn open(path: &str) -> io::Result<LogFile> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let size = file.metadata()?.len();
+    Ok(LogFile { file, size })
+}
+
+/// Shifts `path.N` to `path.N+1`, dropping the oldest, and moves `path` to
+/// `path.1`.
+fn rotate(path: &str, max_files: u32) -> io::Result<()> {
+    if max_files == 0 {
+        return fs::remove_file(path);
+    }
+    let _ = fs::remove_file(format!("{}.{}", path, max_files));
+    for n in (1..max_files).rev() {
+        let from = format!("{}.{}", path, n);
+        if fs::metadata(&from).is_ok() {
+            fs::rename(&from, format!("{}.{}", path, n + 1))?;
+        }
+    }
+    fs::rename(path, format!("{}.1", path))
+}
+
+/// Year, month (1-12), day, hour, minute and second of a UTC time.
+fn civil_time(time: SystemTime) -> (i64, u32, u32, u64, u64, u64) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Days since 1970-01-01 to a proleptic Gregorian date.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
+
+/// `10/Oct/2000:13:55:36 +0000`
+fn clf_time(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = civil_time(time);
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        hour,
+        minute,
+        second
+    )
+}
+
+/// `2000-10-10T13:55:36Z`
+fn rfc3339_time(time: SystemTime) -> String {
+    let (year, month, day, hour, minute, second) = civil_time(time);
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year, month, day, hour, minute, second
+    )
+}
+
+/// Quoted CLF field; `"-"` when missing.
+fn quoted(value: Option<&str>) -> String {
+    match value {
+        Some(value) => format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\"")),
+        None => "\"-\"".to_string(),
+    }
+}
+
+impl<'a> AccessEntry<'a> {
+    pub fn new(
+        peer_addr: Option<IpAddr>,
+        request: Option<&'a Request>,
+        response: &Response,
+        latency: Duration,
+    ) -> Self {
+        AccessEntry {
+            time: SystemTime::now(),
+            peer_addr,
+            request,
+            status: response.status,
+            bytes: response.body.len(),
+            latency,
+        }
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.request.and_then(|request| request.header(name))
+    }
+
+    fn line(&self, format: AccessLogFormat) -> String {
+        let peer = self
+            .peer_addr
+            .map_or_else(|| "-".to_string(), |ip| ip.to_string());
+        let id = self.request.map_or("-", |request| request.id.as_str());
+        let latency_ms = self.latency.as_secs_f64() * 1000.0;
+        if format == AccessLogFormat::Json {
+            return json!({
+                "time": rfc3339_time(self.time),
+                "peer_addr": peer,
+                "method": self.request.map(|request| &request.method),
+                "path": self.request.map(|request| &request.path),
+                "status": self.status,
+                "bytes": self.bytes,
+                "latency_ms": (latency_ms * 1000.0).round() / 1000.0,
+                "request_id": id,
+                "referer": self.header("referer"),
+                "user_agent": self.header("user-agent"),
+            })
+            .to_string();
+        }
+
+        let request_line = self
+            .request
+            .map(|request| format!("{} {} {}", request.method, request.path, request.version));
+        let mut line = format!(
+            "{} - - [{}] {} {} {}",
+            peer,
+            clf_time(self.time),
+            quoted(request_line.as_deref()),
+            self.status,
+            self.bytes
+        );
+        if format == AccessLogFormat::Combined {
+            line.push_str(&format!(
+                " {} {}",
+                quoted(self.header("referer")),
+                quoted(self.header("user-agent"))
+            ));
+        }
+        line.push_str(&format!(" {} {:.3}ms", id, latency_ms));
+        line
+    }
+}
+
+impl AccessLog {
+    pub fn new(config: &AccessLogConfig) -> Self {
+        let file = match &config.path {
+            Some(path) if config.enabled => match open(path) {
+                Ok(file) => Some(file),
+                Err(e) => {
+                    error!("Failed to open access log {}: {}", path, e);
+                    None
+                }
+            },
+            _ => None,
+        };
+        AccessLog {
+            config: config.clone(),
+            file: Mutex::new(file),
+        }
+    }
+
+    pub fn record(&self, entry: &AccessEntry) {
+        if !self.config.enabled {
+            return;
+        }
+        let mut line = entry.line(self.config.format);
+        line.push('\n');
+        let Some(path) = &self.config.path else {
+            print!("{}", line);
+            return;
+        };
+        if let Err(e) = self.append(path, line.as_bytes()) {
+            error!("Failed to write access log {}: {}", path, e);
+        }
+    }
+
+    fn append(&self, path: &str, line: &[u8]) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(current) = file.as_ref()
+            && current.size > 0
+            && current.size + line.len() as u64 > self.config.max_bytes
+        {
+            *file = None;
+            rotate(path, self.config.max_files)?;
+        }
+        // Reopens after a rotation or an earlier failure to open.
+        let current = match file.as_mut() {
+            Some(current) => current,
+            None => file.insert(open(path)?),
+        };
+        current.file.write_all(line)?;
+        current.size += line.len() as u64;
+        Ok(())
+    }
+}
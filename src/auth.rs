@@ -0,0 +1,163 @@
+use crate::config::{AuthConfig, Role};
+use crate::jwt::{Claims, JwtVerifier};
+use crate::request::Request;
+use crate::response::Response;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tracing::debug;
+
+struct ApiKey {
+    name: String,
+    hash: [u8; 32],
+    role: Role,
+}
+
+/// The caller a request was authenticated as.
+#[derive(Clone, Debug)]
+pub struct Principal {
+    /// The API key's name, or the token's `sub`.
+    pub name: String,
+    pub role: Role,
+    /// Set when the caller sent a JWT.
+    pub claims: Option<Claims>,
+}
+
+/// Checks API keys and JWTs against `[auth]` and the role each route needs.
+pub struct Authenticator {
+    config: AuthConfig,
+    keys: Vec<ApiKey>,
+    /// `None` when JWTs are disabled.
+    jwt: Option<JwtVerifier>,
+}
+
+/// Hex SHA-256 of `key`, as `[[auth.keys]]` stores it.
+pub fn hash_key(key: &str) -> String {
+    Sha256::digest(key.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+fn decode_hex(hex: &str) -> Option<[u8; 32]> {
+    let mut bytes = [0; 32];
+    if hex.len() != 64 {
+        return None;
+    }
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn unauthorized() -> Response {
+    Response::text(401, "Unauthorized").with_header("WWW-Authenticate", "Bearer")
+}
+
+impl Authenticator {
+    /// Fails when `[auth.jwt]` is enabled and one of its keys cannot be
+    /// loaded.
+    pub fn new(config: &AuthConfig) -> Result<Self, String> {
+        let jwt = if config.jwt.enabled {
+            let verifier = JwtVerifier::new(&config.jwt)
+                .map_err(|e| format!("Failed to load JWT keys: {}", e))?;
+            Some(verifier)
+        } else {
+            None
+        };
+        Ok(Authenticator {
+            config: config.clone(),
+            keys: config
+                .keys
+                .iter()
+                .filter_map(|key| {
+                    Some(ApiKey {
+                        name: key.name.clone(),
+                        hash: decode_hex(&key.sha256)?,
+                        role: key.role,
+                    })
+                })
+                .collect(),
+            jwt,
+        })
+    }
+
+    /// Who the request's API key or JWT belongs to; `Ok(None)` when it has
+    /// neither.
+    fn authenticate(&self, request: &Request) -> Result<Option<Principal>, Response> {
+        let presented = request
+            .header(&self.config.header)
+            .map(|key| (key, false))
+            .or_else(|| request.bearer_token().map(|token| (token, true)));
+        let Some((credential, bearer)) = presented else {
+            return Ok(None);
+        };
+        if bearer && self.config.jwt.enabled && credential.matches('.').count() == 2 {
+            let Some(jwt) = &self.jwt else {
+                return Err(unauthorized());
+            };
+            let claims = jwt.verify(credential).map_err(|e| {
+                debug!("Rejected JWT: {}", e);
+                unauthorized()
+            })?;
+            let name = match claims.get("sub") {
+                Some(Value::String(sub)) => sub.clone(),
+                _ => "jwt".to_string(),
+            };
+            return Ok(Some(Principal {
+                name,
+                role: jwt.role(&claims),
+                claims: Some(claims),
+            }));
+        }
+        let key = self.lookup(credential).ok_or_else(unauthorized)?;
+        Ok(Some(Principal {
+            name: key.name.clone(),
+            role: key.role,
+            claims: None,
+        }))
+    }
+
+    /// Compares against every key so that the time taken does not reveal
+    /// which one matched.
+    fn lookup(&self, key: &str) -> Option<&ApiKey> {
+        let hash = Sha256::digest(key.as_bytes());
+        let mut found = None;
+        for candidate in &self.keys {
+            if bool::from(candidate.hash.ct_eq(&hash[..])) {
+                found = Some(candidate);
+            }
+        }
+        found
+    }
+
+    /// Name of the `[[auth.keys]]` entry whose key the request sends in
+    /// `header`, when auth is enabled.
+    pub fn api_key_name(&self, request: &Request) -> Option<&str> {
+        if !self.config.enabled {
+            return None;
+        }
+        let key = self.lookup(request.header(&self.config.header)?)?;
+        Some(key.name.as_str())
+    }
+
+    /// Lets the request through, possibly as nobody on public routes, or
+    /// returns the 401 or 403 to send instead.
+    pub fn check(&self, request: &Request) -> Result<Option<Principal>, Response> {
+        if !self.config.enabled {
+            return Ok(None);
+        }
+        let required = self
+            .config
+            .required_role(&request.method, request.path_without_query());
+        let principal = match self.authenticate(request)? {
+            Some(principal) => principal,
+            None if required == Role::Public => return Ok(None),
+            None => return Err(unauthorized()),
+        };
+        if principal.role < required {
+            return Err(Response::text(403, "Forbidden"));
+        }
+        Ok(Some(principal))
+    }
+}
@@ -0,0 +1,241 @@
+use http_server::export::ExportArgs;
+use http_server::insert_strategy::InsertStrategy;
+use http_server::populate::PopulateMode;
+use http_server::sink::OutputFormat;
+use std::collections::HashMap;
+
+pub const USAGE: &str = "\
+Usage: http_server [--config <PATH>] [COMMAND]
+
+Options:
+  --config <PATH>   config file (default: $APP_CONFIG, else config.toml);
+                    APP_<SECTION>_<KEY> variables override its keys
+
+Commands:
+  serve          Run the HTTP server (default)
+  generate       Write synthetic rows to a file instead of the database
+                   --count <N>                 rows to generate (required)
+                   --output <PATH>             file to write (required)
+                   --format <csv|ndjson|sql>   output format (default: csv)
+                   --table <NAME>              table from the generation spec (default: person)
+                   --seed <N>                  seed for reproducible output
+                   --rows-per-statement <N>    rows per INSERT for sql (default: 1000)
+                   --gzip                      gzip the output (implied by a .gz path)
+  populate       Insert synthetic rows into the database
+                   --count <N>                 rows to insert (required)
+                   --mode <threads|tokio>      inserter to use (default: tokio, rate limited)
+                   --strategy <prepared|multi_row|load_data>
+                                               how rows are inserted (default: prepared)
+                   --rows-per-statement <N>    rows per INSERT for multi_row (default: 1000)
+                   --table <NAME>              table from the generation spec (default: person)
+                   --seed <N>                  seed for reproducible data
+  migrate        Apply pending schema migrations
+  export         Write a database table to a file
+                   --output <PATH>             file to write (required)
+                   --format <csv|ndjson|sql>   output format (default: csv)
+                   --table <NAME>              table to export (default: person)
+                   --rows-per-statement <N>    rows per INSERT for sql (default: 1000)
+                   --gzip                      gzip the output (implied by a .gz path)
+  check-config   Validate the configuration and exit
+  hash-key       Read an API key from stdin and print the sha256 for [[auth.keys]]";
+
+pub struct Cli {
+    pub config: Option<String>,
+    pub command: Command,
+}
+
+pub enum Command {
+    Serve,
+    Generate(GenerateArgs),
+    Populate(PopulateArgs),
+    Migrate,
+    Export(ExportArgs),
+    CheckConfig,
+    HashKey,
+}
+
+pub struct GenerateArgs {
+    pub count: u32,
+    pub output: String,
+    pub format: OutputFormat,
+    pub table: String,
+    pub seed: Option<u64>,
+    pub gzip: bool,
+}
+
+pub struct PopulateArgs {
+    pub count: u32,
+    pub mode: PopulateMode,
+    pub strategy: InsertStrategy,
+    pub table: String,
+    pub seed: Option<u64>,
+}
+
+/// `--name value`, `--name=value` and bare `--flag` options of one command.
+struct Options {
+    values: HashMap<String, Option<String>>,
+}
+
+impl Options {
+    fn parse(args: &[String], flags: &[&str]) -> Result<Self, String> {
+        let mut values = HashMap::new();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let name = arg
+                .strip_prefix("--")
+                .ok_or_else(|| format!("Unexpected argument: {}", arg))?;
+            let (name, value) = match name.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None if flags.contains(&name) => (name, None),
+                None => match args.next() {
+                    Some(value) => (name, Some(value.clone())),
+                    None => return Err(format!("Missing value for --{}", name)),
+                },
+            };
+            values.insert(name.to_string(), value);
+        }
+        Ok(Options { values })
+    }
+
+    fn flag(&mut self, name: &str) -> bool {
+        self.values.remove(name).is_some()
+    }
+
+    fn value(&mut self, name: &str) -> Option<String> {
+        self.values.remove(name).flatten()
+    }
+
+    fn required(&mut self, name: &str) -> Result<String, String> {
+        self.value(name)
+            .ok_or_else(|| format!("Missing required option --{}", name))
+    }
+
+    fn table(&mut self) -> String {
+        self.value("table").unwrap_or_else(|| "person".to_string())
+    }
+
+    fn format(&mut self) -> Result<OutputFormat, String> {
+        let rows_per_statement = self.parsed("rows-per-statement")?.unwrap_or(1000);
+        OutputFormat::parse(
+            &self.value("format").unwrap_or_else(|| "csv".to_string()),
+            rows_per_statement,
+        )
+    }
+
+    fn parsed<T: std::str::FromStr>(&mut self, name: &str) -> Result<Option<T>, String> {
+        match self.value(name) {
+            Some(value) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid value for --{}: {}", name, value)),
+            None => Ok(None),
+        }
+    }
+
+    /// Fails on options the command did not consume.
+    fn finish(self) -> Result<(), String> {
+        match self.values.into_keys().next() {
+            Some(name) => Err(format!("Unknown option --{}", name)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Splits off the global options that come before the command.
+pub fn parse(args: &[String]) -> Result<Cli, String> {
+    let mut end = 0;
+    while let Some(arg) = args.get(end).filter(|arg| arg.starts_with("--")) {
+        end += if arg.contains('=') { 1 } else { 2 };
+    }
+    let end = end.min(args.len());
+    let mut options = Options::parse(&args[..end], &[])?;
+    let config = options.value("config");
+    options.finish()?;
+    Ok(Cli {
+        config,
+        command: parse_command(&args[end..])?,
+    })
+}
+
+fn parse_command(args: &[String]) -> Result<Command, String> {
+    let Some((command, rest)) = args.split_first() else {
+        return Ok(Command::Serve);
+    };
+    match command.as_str() {
+        "serve" => {
+            Options::parse(rest, &[])?.finish()?;
+            Ok(Command::Serve)
+        }
+        "generate" => {
+            let mut options = Options::parse(rest, &["gzip"])?;
+            let count = options
+                .parsed("count")?
+                .ok_or("Missing required option --count")?;
+            let output = options.required("output")?;
+            let format = options.format()?;
+            let table = options.table();
+            let seed = options.parsed("seed")?;
+            let gzip = options.flag("gzip");
+            options.finish()?;
+            Ok(Command::Generate(GenerateArgs {
+                count,
+                output,
+                format,
+                table,
+                seed,
+                gzip,
+            }))
+        }
+        "populate" => {
+            let mut options = Options::parse(rest, &[])?;
+            let count = options
+                .parsed("count")?
+                .ok_or("Missing required option --count")?;
+            let mode = match options.value("mode") {
+                Some(mode) => PopulateMode::parse(&mode)?,
+                None => PopulateMode::Tokio,
+            };
+            let strategy = InsertStrategy::parse(
+                options.value("strategy").as_deref(),
+                options.parsed("rows-per-statement")?,
+            )?;
+            let table = options.table();
+            let seed = options.parsed("seed")?;
+            options.finish()?;
+            Ok(Command::Populate(PopulateArgs {
+                count,
+                mode,
+                strategy,
+                table,
+                seed,
+            }))
+        }
+        "migrate" => {
+            Options::parse(rest, &[])?.finish()?;
+            Ok(Command::Migrate)
+        }
+        "export" => {
+            let mut options = Options::parse(rest, &["gzip"])?;
+            let output = options.required("output")?;
+            let format = options.format()?;
+            let table = options.table();
+            let gzip = options.flag("gzip");
+            options.finish()?;
+            Ok(Command::Export(ExportArgs {
+                table,
+                output,
+                format,
+                gzip,
+            }))
+        }
+        "check-config" => {
+            Options::parse(rest, &[])?.finish()?;
+            Ok(Command::CheckConfig)
+        }
+        "hash-key" => {
+            Options::parse(rest, &[])?.finish()?;
+            Ok(Command::HashKey)
+        }
+        other => Err(format!("Unknown command: {}", other)),
+    }
+}
@@ -0,0 +1,187 @@
+use crate::config::{CompressionConfig, Encoding};
+use crate::request::Request;
+use crate::response::{BodyStream, Response};
+use flate2::Compression;
+use flate2::write::{GzEncoder, ZlibEncoder};
+use std::io::{self, Write};
+use tracing::error;
+
+/// Brotli quality; 11 is the maximum but far slower for little gain.
+const BROTLI_QUALITY: u32 = 5;
+const BROTLI_WINDOW: u32 = 22;
+
+/// The `q` value `accept_encoding` gives `token`: its own entry, else `*`,
+/// else 0.
+fn quality(accept_encoding: &str, token: &str) -> f32 {
+    let mut wildcard = None;
+    for entry in accept_encoding.split(',') {
+        let mut parts = entry.split(';');
+        let name = parts.next().unwrap_or("").trim();
+        let q = parts
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|q| q.trim().parse().ok())
+            .unwrap_or(1.0);
+        if name.eq_ignore_ascii_case(token) {
+            return q;
+        }
+        if name == "*" {
+            wildcard = Some(q);
+        }
+    }
+    wildcard.unwrap_or(0.0)
+}
+
+/// The encoding the client rates highest, ties going to the earlier entry in
+/// `encodings`.
+fn negotiate(encodings: &[Encoding], accept_encoding: &str) -> Option<Encoding> {
+    let mut best: Option<(Encoding, f32)> = None;
+    for &encoding in encodings {
+        let q = quality(accept_encoding, encoding.token());
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((encoding, q));
+        }
+    }
+    best.map(|(encoding, _)| encoding)
+}
+
+fn encode(encoding: Encoding, body: &[u8]) -> io::Result<Vec<u8>> {
+    match encoding {
+        Encoding::Brotli => {
+            let mut encoder =
+                brotli::CompressorWriter::new(Vec::new(), 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+            encoder.write_all(body)?;
+            encoder.flush()?;
+            Ok(encoder.into_inner())
+        }
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body)?;
+            encoder.finish()
+        }
+    }
+}
+
+/// Wraps a streamed body so that it is encoded as it is written.
+fn encode_stream(encoding: Encoding, body: BodyStream) -> BodyStream {
+    Box::new(move |writer: &mut dyn Write| match encoding {
+        Encoding::Brotli => {
+            let mut encoder =
+                brotli::CompressorWriter::new(writer, 4096, BROTLI_QUALITY, BROTLI_WINDOW);
+            body(&mut encoder)?;
+            encoder.flush()?;
+            encoder.into_inner();
+            Ok(())
+        }
+        Encoding::Gzip => {
+            let mut encoder = GzEncoder::new(writer, Compression::default());
+            body(&mut encoder)?;
+            encoder.finish().map(drop)
+        }
+        Encoding::Deflate => {
+            let mut encoder = ZlibEncoder::new(writer, Compression::default());
+            body(&mut encoder)?;
+            encoder.finish().map(drop)
+        }
+    })
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Compresses `response` with the best encoding `request` accepts. Bodies
+/// below `min_bytes`, already encoded or of a skipped content type are left
+/// alone; the rest get `Vary: Accept-Encoding` whether or not they were
+/// compressed. Streamed bodies are always large enough.
+pub fn apply(config: &CompressionConfig, request: &Request, mut response: Response) -> Response {
+    if (response.stream.is_none() && response.body.len() < config.min_bytes)
+        || matches!(response.status, 204 | 304)
+        || header(&response, "Content-Encoding").is_some()
+    {
+        return response;
+    }
+    let content_type = header(&response, "Content-Type")
+        .unwrap_or("")
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    if config.skip_content_types.iter().any(|skip| {
+        let skip = skip.to_ascii_lowercase();
+        if skip.ends_with('/') {
+            content_type.starts_with(&skip)
+        } else {
+            content_type == skip
+        }
+    }) {
+        return response;
+    }
+
+    response = response.with_header("Vary", "Accept-Encoding");
+    let Some(encoding) = request
+        .header("accept-encoding")
+        .and_then(|accept| negotiate(&config.encodings, accept))
+    else {
+        return response;
+    };
+    if let Some(stream) = response.stream.take() {
+        response.stream = Some(encode_stream(encoding, stream));
+        return response.with_header("Content-Encoding", encoding.token());
+    }
+    match encode(encoding, &response.body) {
+        // Sent as it is when encoding does not make it smaller.
+        Ok(body) if body.len() < response.body.len() => {
+            response.body = body;
+            response.with_header("Content-Encoding", encoding.token())
+        }
+        Ok(_) => response,
+        Err(e) => {
+            error!("Failed to {}-encode response: {}", encoding.token(), e);
+            response
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Encoding; 3] = [Encoding::Brotli, Encoding::Gzip, Encoding::Deflate];
+
+    #[test]
+    fn quality_reads_the_token_then_the_wildcard() {
+        assert_eq!(quality("gzip;q=0.5, *;q=0.1", "gzip"), 0.5);
+        assert_eq!(quality("GZIP", "gzip"), 1.0);
+        assert_eq!(quality("gzip;q=0.5, *;q=0.1", "br"), 0.1);
+        assert_eq!(quality("gzip", "br"), 0.0);
+        assert_eq!(quality("gzip; q=bad", "gzip"), 1.0);
+    }
+
+    #[test]
+    fn negotiate_prefers_the_highest_quality() {
+        assert_eq!(
+            negotiate(&ALL, "gzip;q=1.0, br;q=0.8"),
+            Some(Encoding::Gzip)
+        );
+        assert_eq!(negotiate(&ALL, "deflate, gzip"), Some(Encoding::Gzip));
+        assert_eq!(negotiate(&ALL, "*"), Some(Encoding::Brotli));
+    }
+
+    #[test]
+    fn negotiate_respects_refusals_and_the_offered_encodings() {
+        assert_eq!(negotiate(&ALL, "br;q=0, *"), Some(Encoding::Gzip));
+        assert_eq!(negotiate(&ALL, "identity"), None);
+        assert_eq!(negotiate(&ALL, "*;q=0"), None);
+        assert_eq!(negotiate(&[Encoding::Deflate], "br, gzip"), None);
+    }
+}
@@ -1,27 +1,1415 @@
-use serde::Deserialize;
-use std::fs;
+use crate::logging;
+use governor::Quota;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_path_to_error::Segment;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::num::NonZeroU32;
+use std::time::Duration;
+use std::{env, fs};
+use toml::{Table, Value};
 
-#[derive(Deserialize)]
+const DEFAULT_PATH: &str = "config.toml";
+
+/// Prefix of environment variables that override config keys, e.g.
+/// `APP_SERVER_PORT` for `server.port`.
+const ENV_PREFIX: &str = "APP_";
+
+/// Environment variable naming the config file when `--config` is not given.
+const ENV_CONFIG_PATH: &str = "APP_CONFIG";
+
+/// Tables that `APP_*` variables can reach, longest first so that
+/// `APP_POPULATE_RATE_LIMIT_UNIT` lands in `populate.rate_limit`.
+const ENV_SECTIONS: &[&str] = &[
+    "validation.max_lengths",
+    "populate.rate_limit",
+    "auth.jwt",
+    "http_rate_limit",
+    "idempotency",
+    "access_log",
+    "auth",
+    "compression",
+    "cors",
+    "log",
+    "database",
+    "populate",
+    "server",
+    "tls",
+    "validation",
+];
+
+/// Keys that only take effect on restart; a reload that changes any of them
+/// is rejected as a whole.
+const RESTART_KEYS: &[&str] = &[
+    "database",
+    "server.host",
+    "server.port",
+    "tls.enabled",
+    "tls.port",
+    "tls.http",
+];
+
+#[derive(Serialize)]
 pub struct Config {
+    /// File the config was loaded from, watched for reloads.
+    #[serde(skip)]
+    pub path: String,
     pub database: DatabaseConfig,
     pub server: ServerConfig,
-    pub rate_limit: u32,
+    pub populate: PopulateConfig,
+    pub http_rate_limit: HttpRateLimitConfig,
+    pub access_log: AccessLogConfig,
+    pub log: LogConfig,
+    pub tls: TlsConfig,
+    pub auth: AuthConfig,
+    pub cors: CorsConfig,
+    pub compression: CompressionConfig,
+    pub validation: ValidationConfig,
+    pub idempotency: IdempotencyConfig,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct DatabaseConfig {
     pub url: String,
 }
 
-#[derive(Deserialize)]
+impl DatabaseConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if let Err(e) = mysql::Opts::from_url(&self.url) {
+            issues.push(ConfigIssue::new("database.url", e.to_string()));
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct ServerConfig {
     pub host: String,
     pub port: u16,
+    /// How long a connection may take to send its request.
+    pub read_timeout_secs: u64,
+    pub write_timeout_secs: u64,
+    /// How long `/readyz` reports draining before a shutdown stops
+    /// accepting connections.
+    pub drain_secs: u64,
+    /// How long a shutdown then waits for requests still in flight.
+    pub shutdown_timeout_secs: u64,
+    /// Largest request body accepted, as sent.
+    pub max_body_bytes: usize,
+    /// Largest request body accepted after undoing its `Content-Encoding`.
+    pub max_decoded_body_bytes: usize,
+    /// Largest `POST /persons/import` body, both as sent and decoded, in
+    /// place of the two limits above. The whole upload is held in memory.
+    pub max_import_bytes: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            read_timeout_secs: 30,
+            write_timeout_secs: 30,
+            drain_secs: 5,
+            shutdown_timeout_secs: 30,
+            max_body_bytes: 1024 * 1024,
+            max_decoded_body_bytes: 8 * 1024 * 1024,
+            max_import_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.read_timeout_secs == 0 {
+            issues.push(ConfigIssue::new(
+                "server.read_timeout_secs",
+                "must be greater than 0",
+            ));
+        }
+        if self.write_timeout_secs == 0 {
+            issues.push(ConfigIssue::new(
+                "server.write_timeout_secs",
+                "must be greater than 0",
+            ));
+        }
+        if self.max_decoded_body_bytes < self.max_body_bytes {
+            issues.push(ConfigIssue::new(
+                "server.max_decoded_body_bytes",
+                "must be at least server.max_body_bytes",
+            ));
+        }
+    }
+
+    /// Largest body `method` on `path` may send, as sent and decoded.
+    pub fn body_limits(&self, method: &str, path: &str) -> (usize, usize) {
+        if method.eq_ignore_ascii_case("POST") && path == "/persons/import" {
+            (self.max_import_bytes, self.max_import_bytes)
+        } else {
+            (self.max_body_bytes, self.max_decoded_body_bytes)
+        }
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_secs(self.read_timeout_secs)
+    }
+
+    pub fn write_timeout(&self) -> Duration {
+        Duration::from_secs(self.write_timeout_secs)
+    }
+
+    pub fn drain(&self) -> Duration {
+        Duration::from_secs(self.drain_secs)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+pub struct PopulateConfig {
+    /// TOML file with `GenerationSpec` tables that `/populate?table=` can fill.
+    pub spec_file: Option<String>,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+}
+
+/// What one unit of `per_second` in a `RateLimitConfig` counts.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitUnit {
+    Rows,
+    Batches,
+    Bytes,
+}
+
+/// `[populate.rate_limit]`: how fast `DataInserterWithTokio` may write.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub unit: RateLimitUnit,
+    pub per_second: u32,
+    /// Units that may be spent at once after a pause; defaults to `per_second`.
+    pub burst: Option<u32>,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            unit: RateLimitUnit::Rows,
+            per_second: 10_000,
+            burst: None,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        validate_quota("populate.rate_limit", self.per_second, self.burst, issues);
+    }
+
+    pub fn quota(&self) -> Quota {
+        quota(self.per_second, self.burst)
+    }
+}
+
+fn quota(per_second: u32, burst: Option<u32>) -> Quota {
+    let per_second = NonZeroU32::new(per_second).unwrap_or(NonZeroU32::MIN);
+    let burst = burst.and_then(NonZeroU32::new).unwrap_or(per_second);
+    Quota::per_second(per_second).allow_burst(burst)
+}
+
+fn validate_quota(
+    section: &str,
+    per_second: u32,
+    burst: Option<u32>,
+    issues: &mut Vec<ConfigIssue>,
+) {
+    if per_second == 0 {
+        issues.push(ConfigIssue::new(
+            format!("{}.per_second", section),
+            "must be greater than 0",
+        ));
+    }
+    if burst == Some(0) {
+        issues.push(ConfigIssue::new(
+            format!("{}.burst", section),
+            "must be greater than 0",
+        ));
+    }
+}
+
+/// What identifies a client for `[http_rate_limit]`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientKey {
+    Ip,
+    /// The name of the `[[auth.keys]]` entry the request's `auth.header`
+    /// matches, falling back to the peer IP when auth is disabled or the
+    /// key is missing or unknown.
+    ApiKey,
+}
+
+/// `[http_rate_limit]`: per-client request quotas in front of the HTTP API.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpRateLimitConfig {
+    pub enabled: bool,
+    pub key: ClientKey,
+    /// Quota for routes without an entry in `routes`.
+    pub per_second: u32,
+    pub burst: Option<u32>,
+    pub routes: Vec<RouteQuotaConfig>,
+}
+
+/// `[[http_rate_limit.routes]]`: quota for requests whose path starts with
+/// `path`, optionally only for one method. The first match wins.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RouteQuotaConfig {
+    pub method: Option<String>,
+    pub path: String,
+    pub per_second: u32,
+    pub burst: Option<u32>,
+}
+
+impl Default for HttpRateLimitConfig {
+    fn default() -> Self {
+        HttpRateLimitConfig {
+            enabled: true,
+            key: ClientKey::Ip,
+            per_second: 50,
+            burst: Some(100),
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl HttpRateLimitConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        validate_quota("http_rate_limit", self.per_second, self.burst, issues);
+        for (i, route) in self.routes.iter().enumerate() {
+            validate_quota(
+                &format!("http_rate_limit.routes[{}]", i),
+                route.per_second,
+                route.burst,
+                issues,
+            );
+        }
+    }
+
+    pub fn quota(&self) -> Quota {
+        quota(self.per_second, self.burst)
+    }
+}
+
+impl RouteQuotaConfig {
+    pub fn matches(&self, method: &str, path: &str) -> bool {
+        self.method
+            .as_deref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(method))
+            && path.starts_with(&self.path)
+    }
+
+    pub fn quota(&self) -> Quota {
+        quota(self.per_second, self.burst)
+    }
+}
+
+/// Line layout of the access log.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccessLogFormat {
+    /// Common Log Format followed by the request id and latency.
+    Common,
+    /// Combined Log Format (Common plus referer and user agent) followed by
+    /// the request id and latency.
+    Combined,
+    /// One JSON object per line.
+    Json,
+}
+
+/// `[access_log]`: one line per request, to stdout or a rotating file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AccessLogConfig {
+    pub enabled: bool,
+    pub format: AccessLogFormat,
+    /// File to append to; stdout when unset.
+    pub path: Option<String>,
+    /// Size at which the file is rotated to `<path>.1`.
+    pub max_bytes: u64,
+    /// Rotated files kept next to the live one.
+    pub max_files: u32,
+}
+
+impl Default for AccessLogConfig {
+    fn default() -> Self {
+        AccessLogConfig {
+            enabled: true,
+            format: AccessLogFormat::Common,
+            path: None,
+            max_bytes: 10 * 1024 * 1024,
+            max_files: 5,
+        }
+    }
+}
+
+impl AccessLogConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.max_bytes == 0 {
+            issues.push(ConfigIssue::new(
+                "access_log.max_bytes",
+                "must be greater than 0",
+            ));
+        }
+    }
+}
+
+/// `[log]`: diagnostics written to stderr.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// `off`, `error`, `warn`, `info`, `debug` or `trace`.
+    pub level: String,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: "info".to_string(),
+        }
+    }
+}
+
+impl LogConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if let Err(e) = logging::parse_level(&self.level) {
+            issues.push(ConfigIssue::new("log.level", e));
+        }
+    }
+}
+
+/// What the plain HTTP listener on `server.port` does once TLS is enabled.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum PlainHttp {
+    /// Serves the same routes as the HTTPS listener.
+    Serve,
+    /// Answers every request with a permanent redirect to HTTPS.
+    Redirect,
+    /// Not opened at all.
+    Off,
+}
+
+/// `[tls]`: an HTTPS listener on `port`. The certificate and key are
+/// re-read on reload and whenever either file changes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM certificate chain, leaf first.
+    pub cert_path: String,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1).
+    pub key_path: String,
+    pub port: u16,
+    pub http: PlainHttp,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        TlsConfig {
+            enabled: false,
+            cert_path: "cert.pem".to_string(),
+            key_path: "key.pem".to_string(),
+            port: 8443,
+            http: PlainHttp::Serve,
+        }
+    }
+}
+
+impl TlsConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if !self.enabled {
+            if self.http != PlainHttp::Serve {
+                issues.push(ConfigIssue::new("tls.http", "requires tls.enabled"));
+            }
+            return;
+        }
+        for (key, path) in [
+            ("tls.cert_path", &self.cert_path),
+            ("tls.key_path", &self.key_path),
+        ] {
+            if let Err(e) = fs::metadata(path) {
+                issues.push(ConfigIssue::new(key, format!("{}: {}", path, e)));
+            }
+        }
+    }
+}
+
+/// What a caller may do, from least to most. Each role can do everything
+/// the ones before it can.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// No key needed; only meaningful for routes.
+    Public,
+    Reader,
+    Writer,
+    Admin,
+}
+
+/// `[auth]`: API keys, sent in `header` or as `Authorization: Bearer`, and
+/// the role each route needs. Routes without an entry in `routes` need
+/// `admin` for `/populate` and `DELETE`, `reader` for `GET`, `HEAD` and
+/// `POST /persons:batchGet` and `writer` for everything else; `/healthz`,
+/// `/readyz` and `OPTIONS` are public.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    pub enabled: bool,
+    pub header: String,
+    pub keys: Vec<ApiKeyConfig>,
+    pub routes: Vec<RouteRoleConfig>,
+    pub jwt: JwtConfig,
+}
+
+/// `[[auth.keys]]`: one key, stored as the hex SHA-256 of the key that
+/// clients send (see `http_server hash-key`).
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ApiKeyConfig {
+    /// Identifies the key in logs.
+    pub name: String,
+    pub sha256: String,
+    pub role: Role,
+}
+
+/// `[auth.jwt]`: bearer tokens signed with HS256 or RS256, accepted next to
+/// API keys. Key files are re-read on every reload.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct JwtConfig {
+    pub enabled: bool,
+    /// File holding the HS256 shared secret.
+    pub hs256_secret_path: Option<String>,
+    /// RS256 public keys in PEM.
+    pub rs256_key_paths: Vec<String>,
+    /// JWKS file with RS256 keys, picked by the token's `kid`.
+    pub jwks_path: Option<String>,
+    /// Required `iss`, when set.
+    pub issuer: Option<String>,
+    /// Required `aud`, when set.
+    pub audience: Option<String>,
+    /// Clock skew allowed when checking `exp` and `nbf`.
+    pub leeway_secs: u64,
+    /// Claim holding a role or a list of roles.
+    pub role_claim: String,
+    /// Role of tokens without `role_claim`.
+    pub default_role: Role,
+}
+
+impl Default for JwtConfig {
+    fn default() -> Self {
+        JwtConfig {
+            enabled: false,
+            hs256_secret_path: None,
+            rs256_key_paths: Vec::new(),
+            jwks_path: None,
+            issuer: None,
+            audience: None,
+            leeway_secs: 30,
+            role_claim: "role".to_string(),
+            default_role: Role::Reader,
+        }
+    }
+}
+
+impl JwtConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if !self.enabled {
+            return;
+        }
+        if self.hs256_secret_path.is_none()
+            && self.rs256_key_paths.is_empty()
+            && self.jwks_path.is_none()
+        {
+            issues.push(ConfigIssue::new(
+                "auth.jwt",
+                "needs hs256_secret_path, rs256_key_paths or jwks_path",
+            ));
+        }
+        let paths = self
+            .hs256_secret_path
+            .iter()
+            .map(|path| ("auth.jwt.hs256_secret_path".to_string(), path))
+            .chain(
+                self.rs256_key_paths
+                    .iter()
+                    .enumerate()
+                    .map(|(i, path)| (format!("auth.jwt.rs256_key_paths[{}]", i), path)),
+            )
+            .chain(
+                self.jwks_path
+                    .iter()
+                    .map(|path| ("auth.jwt.jwks_path".to_string(), path)),
+            );
+        for (key, path) in paths {
+            if let Err(e) = fs::metadata(path) {
+                issues.push(ConfigIssue::new(key, format!("{}: {}", path, e)));
+            }
+        }
+        if self.default_role == Role::Public {
+            issues.push(ConfigIssue::new(
+                "auth.jwt.default_role",
+                "must be reader, writer or admin",
+            ));
+        }
+    }
+}
+
+/// `[[auth.routes]]`: role needed for requests to `path` or anything below
+/// it, optionally only for one method. The first match wins.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RouteRoleConfig {
+    pub method: Option<String>,
+    pub path: String,
+    pub role: Role,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        AuthConfig {
+            enabled: false,
+            header: "X-Api-Key".to_string(),
+            keys: Vec::new(),
+            routes: Vec::new(),
+            jwt: JwtConfig::default(),
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.enabled && self.keys.is_empty() && !self.jwt.enabled {
+            issues.push(ConfigIssue::new(
+                "auth.keys",
+                "must list at least one key when auth is enabled without auth.jwt",
+            ));
+        }
+        self.jwt.validate(issues);
+        for (i, key) in self.keys.iter().enumerate() {
+            if key.sha256.len() != 64 || !key.sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+                issues.push(ConfigIssue::new(
+                    format!("auth.keys[{}].sha256", i),
+                    "must be 64 hex digits",
+                ));
+            }
+            if key.role == Role::Public {
+                issues.push(ConfigIssue::new(
+                    format!("auth.keys[{}].role", i),
+                    "must be reader, writer or admin",
+                ));
+            }
+        }
+    }
+
+    /// Role needed for `method` on `path`.
+    pub fn required_role(&self, method: &str, path: &str) -> Role {
+        if let Some(route) = self.routes.iter().find(|route| route.matches(method, path)) {
+            return route.role;
+        }
+        match method.to_ascii_uppercase().as_str() {
+            _ if path == "/healthz" || path == "/readyz" => Role::Public,
+            _ if path.starts_with("/populate") => Role::Admin,
+            // A read sent as POST so the ids fit in the body.
+            _ if path == "/persons:batchGet" => Role::Reader,
+            "DELETE" => Role::Admin,
+            "GET" | "HEAD" => Role::Reader,
+            "OPTIONS" => Role::Public,
+            _ => Role::Writer,
+        }
+    }
+}
+
+impl RouteRoleConfig {
+    /// Whether the route covers `path`: `/persons` covers `/persons` and
+    /// `/persons/42` but not `/persons:batchGet` or `/personsx`.
+    pub fn matches(&self, method: &str, path: &str) -> bool {
+        self.method
+            .as_deref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(method))
+            && path.strip_prefix(&self.path).is_some_and(|rest| {
+                rest.is_empty() || rest.starts_with('/') || self.path.ends_with('/')
+            })
+    }
+}
+
+/// `[cors]`: lets browser apps on other origins call the API. Preflights are
+/// answered for every registered route.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CorsConfig {
+    pub enabled: bool,
+    /// Origins such as `https://app.example`, or `*` for any.
+    pub allowed_origins: Vec<String>,
+    /// Methods a preflight may allow; every method of the matched route when
+    /// empty.
+    pub allowed_methods: Vec<String>,
+    /// Request headers a preflight allows; `*` allows whatever is asked for.
+    pub allowed_headers: Vec<String>,
+    /// Response headers that scripts may read.
+    pub expose_headers: Vec<String>,
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight.
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        CorsConfig {
+            enabled: false,
+            allowed_origins: Vec::new(),
+            allowed_methods: Vec::new(),
+            allowed_headers: [
+                "Authorization",
+                "Content-Type",
+                "Idempotency-Key",
+                "X-Api-Key",
+                "X-Request-Id",
+            ]
+            .map(String::from)
+            .to_vec(),
+            expose_headers: [
+                "X-Request-Id",
+                "Retry-After",
+                "RateLimit-Limit",
+                "RateLimit-Remaining",
+                "RateLimit-Reset",
+                "Idempotent-Replayed",
+            ]
+            .map(String::from)
+            .to_vec(),
+            allow_credentials: false,
+            max_age_secs: 600,
+        }
+    }
+}
+
+impl CorsConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        for (i, origin) in self.allowed_origins.iter().enumerate() {
+            let valid = origin == "*"
+                || origin
+                    .strip_prefix("https://")
+                    .or_else(|| origin.strip_prefix("http://"))
+                    .is_some_and(|host| !host.is_empty() && !host.contains('/'));
+            if !valid {
+                issues.push(ConfigIssue::new(
+                    format!("cors.allowed_origins[{}]", i),
+                    "must be * or scheme://host[:port] without a path",
+                ));
+            }
+        }
+        if self.allow_credentials && self.allowed_origins.iter().any(|origin| origin == "*") {
+            issues.push(ConfigIssue::new(
+                "cors.allow_credentials",
+                "cannot be used with the * origin",
+            ));
+        }
+    }
+
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins
+            .iter()
+            .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin))
+    }
+}
+
+/// A `Content-Encoding` the server can produce.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    #[serde(rename = "br")]
+    Brotli,
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    /// The token used in `Accept-Encoding` and `Content-Encoding`.
+    pub fn token(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+}
+
+/// `[compression]`: compresses response bodies for clients that accept it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    /// Bodies smaller than this are sent as they are.
+    pub min_bytes: usize,
+    /// Encodings to offer, preferred first when the client has no preference.
+    pub encodings: Vec<Encoding>,
+    /// Content types, or prefixes ending in `/`, that are already compressed.
+    pub skip_content_types: Vec<String>,
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        CompressionConfig {
+            enabled: true,
+            min_bytes: 1024,
+            encodings: vec![Encoding::Brotli, Encoding::Gzip, Encoding::Deflate],
+            skip_content_types: [
+                "image/",
+                "video/",
+                "audio/",
+                "application/gzip",
+                "application/zip",
+                "application/zstd",
+                "application/x-bzip2",
+            ]
+            .map(String::from)
+            .to_vec(),
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.enabled && self.encodings.is_empty() {
+            issues.push(ConfigIssue::new(
+                "compression.encodings",
+                "must list at least one encoding when compression is enabled",
+            ));
+        }
+    }
+}
+
+/// A `person` field that `[validation]` rules can name.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PersonField {
+    Name,
+    Email,
+    Phone,
+    Address,
+    City,
+    State,
+}
+
+impl PersonField {
+    pub const ALL: [PersonField; 6] = [
+        PersonField::Name,
+        PersonField::Email,
+        PersonField::Phone,
+        PersonField::Address,
+        PersonField::City,
+        PersonField::State,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PersonField::Name => "name",
+            PersonField::Email => "email",
+            PersonField::Phone => "phone",
+            PersonField::Address => "address",
+            PersonField::City => "city",
+            PersonField::State => "state",
+        }
+    }
+}
+
+/// `[validation]`: rules person fields must pass when they are created,
+/// updated or imported. Empty fields are only checked against `required`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ValidationConfig {
+    /// Fields that may not be empty.
+    pub required: Vec<PersonField>,
+    /// Whether emails must look like `local@domain.tld`.
+    pub check_email: bool,
+    /// Digits a phone number needs; besides digits it may only hold spaces,
+    /// `-`, `.`, parentheses and a leading `+`.
+    pub phone_min_digits: usize,
+    pub phone_max_digits: usize,
+    /// Accepted `state` codes; empty accepts any.
+    pub states: Vec<String>,
+    pub max_lengths: MaxLengths,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        ValidationConfig {
+            required: vec![PersonField::Name, PersonField::Email],
+            check_email: true,
+            phone_min_digits: 7,
+            phone_max_digits: 15,
+            states: [
+                "AL", "AK", "AZ", "AR", "CA", "CO", "CT", "DE", "FL", "GA", "HI", "ID", "IL", "IN",
+                "IA", "KS", "KY", "LA", "ME", "MD", "MA", "MI", "MN", "MS", "MO", "MT", "NE", "NV",
+                "NH", "NJ", "NM", "NY", "NC", "ND", "OH", "OK", "OR", "PA", "RI", "SC", "SD", "TN",
+                "TX", "UT", "VT", "VA", "WA", "WV", "WI", "WY", "DC", "AS", "GU", "MP", "PR", "VI",
+            ]
+            .map(String::from)
+            .to_vec(),
+            max_lengths: MaxLengths::default(),
+        }
+    }
+}
+
+impl ValidationConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        let columns = MaxLengths::default();
+        for field in PersonField::ALL {
+            let max = self.max_lengths.get(field);
+            if max == 0 || max > columns.get(field) {
+                issues.push(ConfigIssue::new(
+                    format!("validation.max_lengths.{}", field.as_str()),
+                    format!("must be between 1 and {}", columns.get(field)),
+                ));
+            }
+        }
+        if self.phone_min_digits > self.phone_max_digits {
+            issues.push(ConfigIssue::new(
+                "validation.phone_min_digits",
+                "must not exceed validation.phone_max_digits",
+            ));
+        }
+        for (i, state) in self.states.iter().enumerate() {
+            if state.is_empty() || state.chars().count() > self.max_lengths.state {
+                issues.push(ConfigIssue::new(
+                    format!("validation.states[{}]", i),
+                    "must fit validation.max_lengths.state",
+                ));
+            }
+        }
+    }
+}
+
+/// `[validation.max_lengths]`: most characters each field may hold. The
+/// defaults are the `person` column sizes, which are also the maximums.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct MaxLengths {
+    pub name: usize,
+    pub email: usize,
+    pub phone: usize,
+    pub address: usize,
+    pub city: usize,
+    pub state: usize,
+}
+
+impl Default for MaxLengths {
+    fn default() -> Self {
+        MaxLengths {
+            name: 255,
+            email: 255,
+            phone: 32,
+            address: 255,
+            city: 128,
+            state: 2,
+        }
+    }
+}
+
+impl MaxLengths {
+    pub fn get(&self, field: PersonField) -> usize {
+        match field {
+            PersonField::Name => self.name,
+            PersonField::Email => self.email,
+            PersonField::Phone => self.phone,
+            PersonField::Address => self.address,
+            PersonField::City => self.city,
+            PersonField::State => self.state,
+        }
+    }
+}
+
+/// `[idempotency]`: a `POST` sent again with the same key header is answered
+/// with the first response instead of being run twice. Keys are scoped to
+/// the authenticated caller, or to the client address when there is none,
+/// and are only kept in memory.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    pub enabled: bool,
+    pub header: String,
+    /// How long a key and its response are kept.
+    pub ttl_secs: u64,
+    /// Keys kept at once; past it the ones closest to expiring are dropped.
+    pub max_keys: usize,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        IdempotencyConfig {
+            enabled: true,
+            header: "Idempotency-Key".to_string(),
+            ttl_secs: 24 * 60 * 60,
+            max_keys: 10_000,
+        }
+    }
+}
+
+impl IdempotencyConfig {
+    pub fn validate(&self, issues: &mut Vec<ConfigIssue>) {
+        if self.header.is_empty() {
+            issues.push(ConfigIssue::new("idempotency.header", "must not be empty"));
+        }
+        if self.ttl_secs == 0 {
+            issues.push(ConfigIssue::new(
+                "idempotency.ttl_secs",
+                "must be greater than 0",
+            ));
+        }
+        if self.max_keys == 0 {
+            issues.push(ConfigIssue::new(
+                "idempotency.max_keys",
+                "must be greater than 0",
+            ));
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_secs)
+    }
+}
+
+/// One bad key in the merged configuration.
+pub struct ConfigIssue {
+    pub key: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn new(key: impl Into<String>, message: impl Into<String>) -> Self {
+        ConfigIssue {
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+pub enum ConfigError {
+    Read {
+        path: String,
+        error: io::Error,
+    },
+    Syntax {
+        path: String,
+        error: toml::de::Error,
+    },
+    Invalid {
+        path: String,
+        issues: Vec<ConfigIssue>,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, error } => write!(f, "Failed to read {}: {}", path, error),
+            ConfigError::Syntax { path, error } => write!(f, "Failed to parse {}: {}", path, error),
+            ConfigError::Invalid { path, issues } => {
+                write!(f, "Invalid configuration in {}:", path)?;
+                for issue in issues {
+                    write!(f, "\n  {}: {}", issue.key, issue.message)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 impl Config {
-    pub fn load() -> Self {
-        let content = fs::read_to_string("config.toml").expect("Failed to read config.toml");
-        toml::from_str(&content).expect("Failed to parse config.toml")
+    /// Loads `path`, else `$APP_CONFIG`, else `config.toml`, then applies
+    /// `APP_*` overrides. Only an explicitly named file has to exist.
+    pub fn load(path: Option<&str>) -> Result<Self, ConfigError> {
+        let explicit = path
+            .map(str::to_string)
+            .or_else(|| env::var(ENV_CONFIG_PATH).ok());
+        let path = explicit.clone().unwrap_or_else(|| DEFAULT_PATH.to_string());
+        let mut table = match fs::read_to_string(&path) {
+            Ok(content) => content
+                .parse::<Table>()
+                .map_err(|error| ConfigError::Syntax {
+                    path: path.clone(),
+                    error,
+                })?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && explicit.is_none() => Table::new(),
+            Err(error) => return Err(ConfigError::Read { path, error }),
+        };
+        let sources = apply_env_overrides(&mut table, env::vars());
+
+        let mut issues = Vec::new();
+        let config = Config::from_table(path.clone(), table, &mut issues);
+        for issue in &mut issues {
+            if let Some(var) = sources.get(&issue.key) {
+                issue.message.push_str(&format!(" (set by {})", var));
+            }
+        }
+        match config {
+            Some(config) if issues.is_empty() => Ok(config),
+            _ => Err(ConfigError::Invalid { path, issues }),
+        }
+    }
+
+    /// Deserializes and validates every section on its own so that one bad
+    /// key does not hide the others.
+    fn from_table(path: String, mut table: Table, issues: &mut Vec<ConfigIssue>) -> Option<Self> {
+        let database: Option<DatabaseConfig> = section(&mut table, "database", issues);
+        let server: Option<ServerConfig> = section(&mut table, "server", issues);
+        let populate: Option<PopulateConfig> = section(&mut table, "populate", issues);
+        let http_rate_limit: Option<HttpRateLimitConfig> =
+            section(&mut table, "http_rate_limit", issues);
+        let access_log: Option<AccessLogConfig> = section(&mut table, "access_log", issues);
+        let log: Option<LogConfig> = section(&mut table, "log", issues);
+        let tls: Option<TlsConfig> = section(&mut table, "tls", issues);
+        let auth: Option<AuthConfig> = section(&mut table, "auth", issues);
+        let cors: Option<CorsConfig> = section(&mut table, "cors", issues);
+        let compression: Option<CompressionConfig> = section(&mut table, "compression", issues);
+        let validation: Option<ValidationConfig> = section(&mut table, "validation", issues);
+        let idempotency: Option<IdempotencyConfig> = section(&mut table, "idempotency", issues);
+        for key in table.keys() {
+            issues.push(ConfigIssue::new(key.as_str(), "unknown section"));
+        }
+        if let Some(database) = &database {
+            database.validate(issues);
+        }
+        if let Some(server) = &server {
+            server.validate(issues);
+        }
+        if let Some(populate) = &populate {
+            populate.rate_limit.validate(issues);
+        }
+        if let Some(http_rate_limit) = &http_rate_limit {
+            http_rate_limit.validate(issues);
+        }
+        if let Some(access_log) = &access_log {
+            access_log.validate(issues);
+        }
+        if let Some(log) = &log {
+            log.validate(issues);
+        }
+        if let Some(auth) = &auth {
+            auth.validate(issues);
+        }
+        if let Some(cors) = &cors {
+            cors.validate(issues);
+        }
+        if let Some(compression) = &compression {
+            compression.validate(issues);
+        }
+        if let Some(validation) = &validation {
+            validation.validate(issues);
+        }
+        if let Some(idempotency) = &idempotency {
+            idempotency.validate(issues);
+        }
+        if let Some(tls) = &tls {
+            tls.validate(issues);
+            if tls.enabled
+                && tls.http != PlainHttp::Off
+                && let Some(server) = &server
+                && server.port == tls.port
+            {
+                issues.push(ConfigIssue::new("tls.port", "must differ from server.port"));
+            }
+        }
+        Some(Config {
+            path,
+            database: database?,
+            server: server?,
+            populate: populate?,
+            http_rate_limit: http_rate_limit?,
+            access_log: access_log?,
+            log: log?,
+            tls: tls?,
+            auth: auth?,
+            cors: cors?,
+            compression: compression?,
+            validation: validation?,
+            idempotency: idempotency?,
+        })
+    }
+}
+
+impl Config {
+    /// Keys whose values differ in `other`, as `key: old -> new` lines.
+    pub fn changes(&self, other: &Config) -> Vec<String> {
+        let mut changes = Vec::new();
+        diff("", &to_value(self), &to_value(other), &mut changes);
+        changes
+    }
+
+    /// Changed keys that `Server::reload` cannot apply to a running server.
+    pub fn restart_required(changes: &[String]) -> Vec<&str> {
+        changes
+            .iter()
+            .map(|change| change.split(':').next().unwrap_or(""))
+            .filter(|key| {
+                RESTART_KEYS
+                    .iter()
+                    .any(|restart| key == restart || key.starts_with(&format!("{}.", restart)))
+            })
+            .collect()
+    }
+}
+
+fn to_value(config: &Config) -> Value {
+    Value::try_from(config).unwrap_or_else(|_| Value::Table(Table::new()))
+}
+
+fn diff(key: &str, old: &Value, new: &Value, changes: &mut Vec<String>) {
+    let child = |name: &str| {
+        if key.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", key, name)
+        }
+    };
+    let unset = Value::String("(unset)".to_string());
+    match (old, new) {
+        (Value::Table(old), Value::Table(new)) => {
+            let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+            names.sort();
+            names.dedup();
+            for name in names {
+                let old = old.get(name.as_str()).unwrap_or(&unset);
+                let new = new.get(name.as_str()).unwrap_or(&unset);
+                diff(&child(name), old, new, changes);
+            }
+        }
+        (old, new) if old != new => changes.push(format!("{}: {} -> {}", key, old, new)),
+        _ => {}
+    }
+}
+
+/// Sets a key for every `APP_<SECTION>_<KEY>` variable. Values are read as
+/// TOML (`8080`, `true`, `'"quoted"'`) and fall back to plain strings.
+/// Returns which variable set each key.
+fn apply_env_overrides(
+    table: &mut Table,
+    vars: impl Iterator<Item = (String, String)>,
+) -> HashMap<String, String> {
+    let mut sources = HashMap::new();
+    for (var, raw) in vars {
+        let Some(name) = var.strip_prefix(ENV_PREFIX) else {
+            continue;
+        };
+        let name = name.to_ascii_lowercase();
+        let Some((section, key)) = ENV_SECTIONS.iter().find_map(|section| {
+            let prefix = format!("{}_", section.replace('.', "_"));
+            name.strip_prefix(&prefix)
+                .filter(|key| !key.is_empty())
+                .map(|key| (*section, key))
+        }) else {
+            continue;
+        };
+
+        let mut target = &mut *table;
+        for part in section.split('.') {
+            let entry = target
+                .entry(part)
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            target = entry.as_table_mut().unwrap();
+        }
+        let value = format!("v = {}", raw)
+            .parse::<Table>()
+            .ok()
+            .and_then(|mut parsed| parsed.remove("v"))
+            .unwrap_or(Value::String(raw));
+        target.insert(key.to_string(), value);
+        sources.insert(format!("{}.{}", section, key), var);
+    }
+    sources
+}
+
+/// Takes `name` out of `table` and deserializes it, recording each bad key
+/// and dropping it before trying again.
+fn section<T: DeserializeOwned>(
+    table: &mut Table,
+    name: &str,
+    issues: &mut Vec<ConfigIssue>,
+) -> Option<T> {
+    let mut value = table
+        .remove(name)
+        .unwrap_or_else(|| Value::Table(Table::new()));
+    let reported = issues.len();
+    loop {
+        let error = match serde_path_to_error::deserialize::<_, T>(value.clone()) {
+            Ok(parsed) => return (issues.len() == reported).then_some(parsed),
+            Err(error) => error,
+        };
+        let mut path: Vec<Segment> = error.path().iter().cloned().collect();
+        let message = error.inner().message().to_string();
+
+        // Missing fields are reported against the enclosing table.
+        let (message, removable) = if let Some(field) = backticked(&message, "missing field `") {
+            path.push(Segment::Map { key: field });
+            ("is required".to_string(), false)
+        } else if backticked(&message, "unknown field `").is_some() {
+            let expected = message.split_once(", ").map_or("", |(_, rest)| rest);
+            (format!("unknown key, {}", expected), true)
+        } else {
+            (message, true)
+        };
+
+        let key = key_name(name, &path);
+        if issues[reported..].iter().any(|issue| issue.key == key) {
+            return None;
+        }
+        issues.push(ConfigIssue::new(key, message));
+        if !removable || !remove(&mut value, &path) {
+            return None;
+        }
+    }
+}
+
+fn backticked(message: &str, prefix: &str) -> Option<String> {
+    let rest = message.strip_prefix(prefix)?;
+    rest.split_once('`').map(|(field, _)| field.to_string())
+}
+
+fn key_name(section: &str, path: &[Segment]) -> String {
+    let mut key = section.to_string();
+    for segment in path {
+        match segment {
+            Segment::Seq { index } => key.push_str(&format!("[{}]", index)),
+            Segment::Map { key: field } => {
+                key.push('.');
+                key.push_str(field);
+            }
+            Segment::Enum { .. } | Segment::Unknown => {}
+        }
+    }
+    key
+}
+
+fn remove(value: &mut Value, path: &[Segment]) -> bool {
+    let Some((last, parents)) = path.split_last() else {
+        return false;
+    };
+    let mut current = value;
+    for segment in parents {
+        current = match (segment, current) {
+            (Segment::Map { key }, Value::Table(table)) => match table.get_mut(key) {
+                Some(next) => next,
+                None => return false,
+            },
+            (Segment::Seq { index }, Value::Array(array)) => match array.get_mut(*index) {
+                Some(next) => next,
+                None => return false,
+            },
+            _ => return false,
+        };
+    }
+    match (last, current) {
+        (Segment::Map { key }, Value::Table(table)) => table.remove(key).is_some(),
+        (Segment::Seq { index }, Value::Array(array)) if *index < array.len() => {
+            array.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(vars: &[(&str, &str)]) -> (Table, HashMap<String, String>) {
+        let mut table: Table = "[server]\nport = 8080\n".parse().unwrap();
+        let vars = vars
+            .iter()
+            .map(|(var, value)| (var.to_string(), value.to_string()));
+        let sources = apply_env_overrides(&mut table, vars);
+        (table, sources)
+    }
+
+    #[test]
+    fn env_overrides_are_read_as_toml_or_else_as_strings() {
+        let (table, _) = overrides(&[
+            ("APP_SERVER_PORT", "9090"),
+            ("APP_CORS_ENABLED", "true"),
+            ("APP_CORS_ALLOWED_ORIGINS", r#"["https://example.com"]"#),
+            ("APP_DATABASE_URL", "mysql://root@localhost/app"),
+        ]);
+
+        assert_eq!(table["server"]["port"], Value::Integer(9090));
+        assert_eq!(table["cors"]["enabled"], Value::Boolean(true));
+        assert_eq!(
+            table["cors"]["allowed_origins"],
+            Value::Array(vec![Value::from("https://example.com")])
+        );
+        assert_eq!(
+            table["database"]["url"],
+            Value::from("mysql://root@localhost/app")
+        );
+    }
+
+    #[test]
+    fn env_overrides_pick_the_longest_section() {
+        let (table, sources) = overrides(&[
+            ("APP_POPULATE_RATE_LIMIT_UNIT", "minute"),
+            ("APP_POPULATE_SPEC_FILE", "spec.toml"),
+            ("APP_VALIDATION_MAX_LENGTHS_NAME", "50"),
+        ]);
+
+        assert_eq!(
+            table["populate"]["rate_limit"]["unit"],
+            Value::from("minute")
+        );
+        assert_eq!(table["populate"]["spec_file"], Value::from("spec.toml"));
+        assert_eq!(
+            table["validation"]["max_lengths"]["name"],
+            Value::Integer(50)
+        );
+        assert_eq!(
+            sources["populate.rate_limit.unit"],
+            "APP_POPULATE_RATE_LIMIT_UNIT"
+        );
+    }
+
+    #[test]
+    fn other_variables_are_ignored() {
+        let (table, sources) = overrides(&[
+            ("PATH", "/usr/bin"),
+            ("APP_CONFIG", "other.toml"),
+            ("APP_UNKNOWN_KEY", "1"),
+            ("APP_SERVER_", "1"),
+        ]);
+
+        assert_eq!(table, "[server]\nport = 8080\n".parse::<Table>().unwrap());
+        assert!(sources.is_empty());
+    }
+
+    #[test]
+    fn bad_override_values_are_reported_against_their_key() {
+        let (table, sources) = overrides(&[("APP_SERVER_PORT", "abc")]);
+        let mut issues = Vec::new();
+
+        assert!(Config::from_table("config.toml".to_string(), table, &mut issues).is_none());
+        let issue = issues
+            .iter()
+            .find(|issue| issue.key == "server.port")
+            .unwrap();
+        assert_eq!(sources.get(&issue.key).unwrap(), "APP_SERVER_PORT");
     }
 }
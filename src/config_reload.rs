@@ -0,0 +1,96 @@
+use crate::config::Config;
+use crate::server::Server;
+use std::fs;
+use std::thread;
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::time::sleep;
+use tracing::{error, info, warn};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+fn modified(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+fn reload(server: &Server, path: &str, reason: &str) {
+    let config = match Config::load(Some(path)) {
+        Ok(config) => config,
+        Err(e) => {
+            error!(
+                "Config reload on {} failed, keeping current config: {}",
+                reason, e
+            );
+            return;
+        }
+    };
+    match server.reload(config) {
+        Ok(changes) if changes.is_empty() => info!("Config reloaded on {}: no changes", reason),
+        Ok(changes) => {
+            for change in changes {
+                info!("Config reloaded on {}: {}", reason, change);
+            }
+        }
+        Err(e) => warn!("Config reload on {} rejected: {}", reason, e),
+    }
+}
+
+/// Modification times of the TLS certificate and key in use, if any.
+fn certificates_modified(server: &Server) -> Option<(Option<SystemTime>, Option<SystemTime>)> {
+    let config = server.config();
+    config.tls.enabled.then(|| {
+        (
+            modified(&config.tls.cert_path),
+            modified(&config.tls.key_path),
+        )
+    })
+}
+
+/// Reloads the config file on SIGHUP or when its modification time changes,
+/// and the TLS certificate when its files change.
+pub fn watch(server: Server) {
+    thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async move {
+            let path = server.config().path.clone();
+            let mut hangup = match signal(SignalKind::hangup()) {
+                Ok(hangup) => Some(hangup),
+                Err(e) => {
+                    error!("Failed to listen for SIGHUP: {}", e);
+                    None
+                }
+            };
+            let mut last_modified = modified(&path);
+            let mut certificates = certificates_modified(&server);
+            loop {
+                let reason = tokio::select! {
+                    Some(()) = async { hangup.as_mut()?.recv().await } => "SIGHUP",
+                    _ = sleep(POLL_INTERVAL) => {
+                        let current = certificates_modified(&server);
+                        if current != certificates {
+                            certificates = current;
+                            match server.reload_certificates() {
+                                Ok(()) => info!("TLS certificate reloaded"),
+                                Err(e) => error!(
+                                    "TLS certificate reload failed, keeping current certificate: {}",
+                                    e
+                                ),
+                            }
+                        }
+                        let current = modified(&path);
+                        if current == last_modified {
+                            continue;
+                        }
+                        last_modified = current;
+                        "change to the file"
+                    }
+                };
+                reload(&server, &path, reason);
+                certificates = certificates_modified(&server);
+            }
+        });
+    });
+}
@@ -0,0 +1,93 @@
+use crate::config::CorsConfig;
+use crate::request::Request;
+use crate::response::Response;
+
+/// An `OPTIONS` request a browser sends before a cross-origin call.
+pub fn is_preflight(request: &Request) -> bool {
+    request.method.eq_ignore_ascii_case("OPTIONS")
+        && request.header("origin").is_some()
+        && request.header("access-control-request-method").is_some()
+}
+
+/// `Access-Control-Allow-Origin` for `origin`, if it is allowed.
+fn allow_origin<'a>(config: &CorsConfig, origin: &'a str) -> Option<&'a str> {
+    if !config.allows_origin(origin) {
+        return None;
+    }
+    // Credentials need the origin echoed back; `*` is cheaper to cache.
+    let any = config.allowed_origins.iter().any(|allowed| allowed == "*");
+    Some(if any && !config.allow_credentials {
+        "*"
+    } else {
+        origin
+    })
+}
+
+fn with_origin(config: &CorsConfig, response: Response, origin: &str) -> Response {
+    let response = response
+        .with_header("Access-Control-Allow-Origin", origin)
+        .with_header("Vary", "Origin");
+    if config.allow_credentials {
+        response.with_header("Access-Control-Allow-Credentials", "true")
+    } else {
+        response
+    }
+}
+
+/// 204 answer to a preflight for a path served by `methods`, or `None` when
+/// the origin is not allowed so that the request is handled like any other
+/// `OPTIONS`.
+pub fn preflight(config: &CorsConfig, request: &Request, methods: &[&str]) -> Option<Response> {
+    let origin = allow_origin(config, request.header("origin")?)?;
+    let methods: Vec<&str> = methods
+        .iter()
+        .copied()
+        .filter(|method| {
+            config.allowed_methods.is_empty()
+                || config
+                    .allowed_methods
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(method))
+        })
+        .collect();
+    let headers = if config.allowed_headers.iter().any(|header| header == "*") {
+        request
+            .header("access-control-request-headers")
+            .unwrap_or_default()
+            .to_string()
+    } else {
+        config.allowed_headers.join(", ")
+    };
+    let response = Response::text(204, "")
+        .with_header("Access-Control-Allow-Methods", methods.join(", "))
+        .with_header("Access-Control-Allow-Headers", headers)
+        .with_header("Access-Control-Max-Age", config.max_age_secs);
+    Some(with_origin(config, response, origin))
+}
+
+/// Adds the headers that let a script on an allowed origin read `response`,
+/// unless it is a preflight answer that already has them.
+pub fn apply(config: &CorsConfig, request: &Request, response: Response) -> Response {
+    if response
+        .headers
+        .iter()
+        .any(|(name, _)| name == "Access-Control-Allow-Origin")
+    {
+        return response;
+    }
+    let Some(origin) = request
+        .header("origin")
+        .and_then(|origin| allow_origin(config, origin))
+    else {
+        return response;
+    };
+    let response = with_origin(config, response, origin);
+    if config.expose_headers.is_empty() {
+        response
+    } else {
+        response.with_header(
+            "Access-Control-Expose-Headers",
+            config.expose_headers.join(", "),
+        )
+    }
+}
@@ -1,30 +1,73 @@
+use crate::fake_data::{self, Rng};
 use crate::model::person::Person;
-use std::sync::mpsc::Sender;
+use crate::row_generator::{Row, RowGenerator};
+use mysql::Value;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-pub struct DataGenerator;
+const PERSON_COLUMNS: [&str; 7] = [
+    "name", "email", "phone", "address", "city", "state", "version",
+];
+
+/// Generates rows for the `person` table.
+pub struct DataGenerator {
+    seed: u64,
+    columns: Vec<String>,
+}
 
 impl DataGenerator {
-    pub fn new() -> Self {
-        DataGenerator
+    pub fn new(seed: u64) -> Self {
+        DataGenerator {
+            seed,
+            columns: PERSON_COLUMNS.iter().map(|c| c.to_string()).collect(),
+        }
     }
 
-    pub fn generate(&self, count: u32, start_id: u32, tx: Sender<Vec<Person>>) {
-        let mut persons = Vec::with_capacity(count as usize);
-        for i in 0..count {
-            persons.push(Person {
-                id: 0,
-                name: format!("name {}", start_id + i),
-                email: format!("email{}@example.com", start_id + i),
-                phone: format!("{}", start_id + i),
-                address: format!("address {}", start_id + i),
-                city: format!("city {}", start_id + i),
-                state: format!("state {}", start_id + i),
-                version: 0,
-            }); // ID will be set by DB
-        }
-        let send = tx.send(persons);
-        if let Err(e) = send {
-            eprintln!("Failed to send generated data: {}", e);
+    /// Seed for runs that did not ask for one; report it so the run can be repeated.
+    pub fn random_seed() -> u64 {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+        Rng::new(nanos).next_u64()
+    }
+
+    pub fn person(&self, index: u32) -> Person {
+        let mut rng = Rng::for_row(self.seed, index as u64);
+        let first = fake_data::first_name(&mut rng);
+        let last = fake_data::last_name(&mut rng);
+        let (city, state) = fake_data::city_and_state(&mut rng);
+        Person {
+            id: 0, // ID will be set by DB
+            name: format!("{} {}", first, last),
+            email: fake_data::email(&mut rng, first, last, index as u64),
+            phone: fake_data::phone(&mut rng),
+            address: fake_data::street_address(&mut rng),
+            city: city.to_string(),
+            state: state.to_string(),
+            version: 1,
         }
     }
 }
+
+impl RowGenerator for DataGenerator {
+    fn table(&self) -> &str {
+        "person"
+    }
+
+    fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    fn row(&self, index: u64) -> Row {
+        let p = self.person(index as u32);
+        vec![
+            Value::from(p.name),
+            Value::from(p.email),
+            Value::from(p.phone),
+            Value::from(p.address),
+            Value::from(p.city),
+            Value::from(p.state),
+            Value::from(p.version),
+        ]
+    }
+}
@@ -1,8 +1,67 @@
-use crate::model::person::Person;
-use mysql::{Pool, TxOpts, params, prelude::*};
+use crate::insert_strategy::InsertStrategy;
+use crate::metrics;
+use crate::row_generator::{Row, RowGenerator};
+use mysql::{Pool, TxOpts};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Barrier, Mutex};
 use std::time::{Duration, Instant};
+use tracing::{error, info_span, warn};
+
+/// Lets the inserters of one job commit only when none of them failed, so
+/// that a failed job leaves no rows behind unless a commit itself fails.
+pub struct CommitGate {
+    failed: AtomicBool,
+    barrier: Barrier,
+}
+
+/// One inserter's place at a `CommitGate`. Dropping it before `may_commit`,
+/// as an early return or a panic does, counts as a failure.
+pub struct Arrival<'a> {
+    gate: &'a CommitGate,
+    arrived: bool,
+}
+
+impl CommitGate {
+    pub fn new(inserters: usize) -> Self {
+        CommitGate {
+            failed: AtomicBool::new(false),
+            barrier: Barrier::new(inserters),
+        }
+    }
+
+    /// Every inserter has to call this once, before writing anything.
+    pub fn join(&self) -> Arrival<'_> {
+        Arrival {
+            gate: self,
+            arrived: false,
+        }
+    }
+
+    /// Whether an inserter has failed; the others then stop taking rows.
+    pub fn failed(&self) -> bool {
+        self.failed.load(Ordering::SeqCst)
+    }
+}
+
+impl Arrival<'_> {
+    /// Waits for every inserter to finish writing, then tells whether all
+    /// of them succeeded.
+    pub fn may_commit(mut self) -> bool {
+        self.arrived = true;
+        self.gate.barrier.wait();
+        !self.gate.failed()
+    }
+}
+
+impl Drop for Arrival<'_> {
+    fn drop(&mut self) {
+        if !self.arrived {
+            self.gate.failed.store(true, Ordering::SeqCst);
+            self.gate.barrier.wait();
+        }
+    }
+}
 
 pub struct DataInserter {
     pool: Pool,
@@ -13,121 +72,128 @@ impl DataInserter {
         DataInserter { pool }
     }
 
-    pub fn populate(&self, count: u32) -> mysql::Result<Duration> {
+    pub fn populate(
+        &self,
+        generator: Arc<dyn RowGenerator>,
+        count: u32,
+        strategy: InsertStrategy,
+    ) -> Result<Duration, String> {
         let start_time = Instant::now();
-        // const BATCH_SIZE: u32 = 1000;
         const GENERATOR_THREADS: u32 = 4;
         const INSERTER_THREADS: u32 = 2;
 
-        let (tx, rx): (Sender<Vec<Person>>, Receiver<Vec<Person>>) = std::sync::mpsc::channel();
+        let job = Arc::new(metrics::global().populate_job());
+        let gate = Arc::new(CommitGate::new(INSERTER_THREADS as usize));
+        let (tx, rx): (Sender<Vec<Row>>, Receiver<Vec<Row>>) = std::sync::mpsc::channel();
         let rx = Arc::new(Mutex::new(rx));
         let mut generator_handles = vec![];
         let mut inserter_handles = vec![];
 
         // Start generator threads
-        let chunk_size =
-            count / GENERATOR_THREADS + if count % GENERATOR_THREADS > 0 { 1 } else { 0 };
+        let chunk_size = count.div_ceil(GENERATOR_THREADS);
         for i in 0..GENERATOR_THREADS {
             let start_id = i * chunk_size;
-            let generate_count = if i == GENERATOR_THREADS - 1 {
-                count - (i * chunk_size)
-            } else {
-                chunk_size
-            };
+            let generate_count = chunk_size.min(count.saturating_sub(start_id));
             if generate_count == 0 {
                 continue;
             }
             let tx = tx.clone();
-            let generator = crate::data_generator::DataGenerator::new();
+            let generator = Arc::clone(&generator);
+            let gate = Arc::clone(&gate);
+            let span = info_span!("generator", start_id, count = generate_count);
             generator_handles.push(std::thread::spawn(move || {
-                generator.generate(generate_count, start_id, tx);
+                let _entered = span.enter();
+                if !gate.failed() {
+                    generator.generate(generate_count, start_id, tx);
+                }
             }));
         }
 
         // Start inserter threads
         let pool = self.pool.clone();
-        for _ in 0..INSERTER_THREADS {
+        for worker in 0..INSERTER_THREADS {
             let rx = Arc::clone(&rx);
             let pool = pool.clone();
-            inserter_handles.push(std::thread::spawn(move || {
-                let mut conn = match pool.get_conn() {
-                    Ok(conn) => conn,
-                    Err(e) => {
-                        eprintln!("Failed to get database connection: {}", e);
-                        return;
-                    }
-                };
-                let tx_opts = TxOpts::default();
-                let mut tx = match conn.start_transaction(tx_opts) {
-                    Ok(tx) => tx,
-                    Err(e) => {
-                        eprintln!("Failed to start transaction: {}", e);
-                        return;
-                    }
-                };
-
-                loop {
-                    let persons = {
-                        let rx = match rx.lock() {
-                            Ok(rx) => rx,
-                            Err(e) => {
-                                eprintln!("Failed to lock receiver: {}", e);
-                                return;
-                            }
-                        };
+            let generator = Arc::clone(&generator);
+            let job = Arc::clone(&job);
+            let gate = Arc::clone(&gate);
+            let span = info_span!("inserter", worker);
+            inserter_handles.push(std::thread::spawn(move || -> Result<(), String> {
+                let _entered = span.enter();
+                let arrival = gate.join();
+                let mut conn = metrics::global()
+                    .get_conn(&pool)
+                    .map_err(|e| format!("Failed to get database connection: {}", e))?;
+                let mut tx = conn
+                    .start_transaction(TxOpts::default())
+                    .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+                // Rows written in `tx`; they only count as inserted once it
+                // commits.
+                let mut pending = 0;
+                while !gate.failed() {
+                    let rows = {
+                        let rx = rx
+                            .lock()
+                            .map_err(|e| format!("Failed to lock receiver: {}", e))?;
                         match rx.recv() {
-                            Ok(persons) => persons,
+                            Ok(rows) => rows,
                             Err(_) => break, // Channel closed
                         }
                     };
+                    job.generated(rows.len());
 
-                    let params: Vec<_> = persons
-                        .into_iter()
-                        .map(|p| {
-                            params! {
-                                "name" => p.name,
-                                "email" => p.email,
-                                "phone" => p.phone,
-                                "address" => p.address,
-                                "city" => p.city,
-                                "state" => p.state,
-                                "version" => p.version,
-                            }
-                        })
-                        .collect();
-
-                    if let Err(e) = tx.exec_batch(
-                        "INSERT INTO person (name, email, phone, address, city, state, version) VALUES (:name, :email, :phone, :address, :city, :state, :version)",
-                        params.iter(),
-                    ) {
-                        eprintln!("Failed to execute batch insert: {}", e);
-                        return;
+                    if let Err(e) =
+                        strategy.insert(&mut tx, generator.table(), generator.columns(), &rows)
+                    {
+                        job.failed(pending + rows.len());
+                        return Err(format!("Failed to execute batch insert: {}", e));
                     }
+                    pending += rows.len();
                 }
 
+                if !arrival.may_commit() {
+                    warn!(
+                        "Rolling back {} rows after another inserter failed",
+                        pending
+                    );
+                    job.failed(pending);
+                    return Ok(());
+                }
                 if let Err(e) = tx.commit() {
-                    eprintln!("Failed to commit transaction: {}", e);
+                    job.failed(pending);
+                    return Err(format!("Failed to commit transaction: {}", e));
                 }
+                job.inserted(pending);
+                Ok(())
             }));
         }
 
         // Wait for generators to finish
         for handle in generator_handles {
             if let Err(e) = handle.join() {
-                eprintln!("Generator thread failed: {:?}", e);
+                error!("Generator thread failed: {:?}", e);
             }
         }
 
         // Drop the original sender to close the channel
         drop(tx);
 
-        // Wait for inserters to finish
+        // Wait for inserters to finish; the job failed if any of them did.
+        let mut failure = None;
         for handle in inserter_handles {
-            if let Err(e) = handle.join() {
-                eprintln!("Inserter thread failed: {:?}", e);
+            let result = handle
+                .join()
+                .unwrap_or_else(|e| Err(format!("Inserter thread panicked: {:?}", e)));
+            if let Err(e) = result {
+                error!("{}", e);
+                failure.get_or_insert(e);
             }
         }
 
-        Ok(start_time.elapsed())
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(start_time.elapsed()),
+        }
     }
 }
@@ -1,13 +1,26 @@
-use crate::config::Config;
-use crate::model::person::Person;
-use governor::{Quota, RateLimiter};
-use mysql::{Pool, TxOpts, params, prelude::*};
-use nonzero_ext::nonzero;
+use crate::config::{Config, RateLimitUnit};
+use crate::data_inserter::CommitGate;
+use crate::insert_strategy::InsertStrategy;
+use crate::metrics;
+use crate::row_generator::{Row, RowGenerator};
+use governor::RateLimiter;
+use mysql::{Pool, TxOpts};
 use std::num::NonZeroU32;
 use std::sync::mpsc::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
 use tokio::task;
+use tracing::{debug, error, info_span, warn};
+
+/// Units of the populate rate limit spent on writing `batch`.
+fn batch_cost(unit: RateLimitUnit, batch: &[Row]) -> u64 {
+    match unit {
+        RateLimitUnit::Rows => batch.len() as u64,
+        RateLimitUnit::Batches => 1,
+        RateLimitUnit::Bytes => batch.iter().flatten().map(|value| value.bin_len()).sum(),
+    }
+}
 
 pub struct DataInserterWithTokio {
     pool: Pool,
@@ -19,137 +32,149 @@ impl DataInserterWithTokio {
         DataInserterWithTokio { pool, config }
     }
 
-    pub async fn populate(&self, count: u32) -> mysql::Result<Duration> {
+    pub async fn populate(
+        &self,
+        generator: Arc<dyn RowGenerator>,
+        count: u32,
+        strategy: InsertStrategy,
+    ) -> Result<Duration, String> {
         let start_time = Instant::now();
         const BATCH_SIZE: u32 = 1000;
         const GENERATOR_THREADS: u32 = 10;
         const INSERTER_THREADS: u32 = 2;
 
-         let rate_limit = match NonZeroU32::new(self.config.rate_limit) {
-            Some(nz) => nz,
-            None => nonzero!(100u32), // Default to 100 if rate_limit is 0
-        };
-
-        let quota = Quota::per_second(rate_limit);
+        let unit = self.config.populate.rate_limit.unit;
+        let quota = self.config.populate.rate_limit.quota();
         let limiter = Arc::new(RateLimiter::direct(quota));
 
-        let (tx, rx): (Sender<Vec<Person>>, Receiver<Vec<Person>>) = std::sync::mpsc::channel();
-        let rx: Arc<Mutex<Receiver<Vec<Person>>>> = Arc::new(Mutex::new(rx));
+        let job = Arc::new(metrics::global().populate_job());
+        let gate = Arc::new(CommitGate::new(INSERTER_THREADS as usize));
+        let (tx, rx): (Sender<Vec<Row>>, Receiver<Vec<Row>>) = std::sync::mpsc::channel();
+        let rx: Arc<Mutex<Receiver<Vec<Row>>>> = Arc::new(Mutex::new(rx));
         let mut generator_handles = vec![];
-        let chunk_size =
-            count / GENERATOR_THREADS + if count % GENERATOR_THREADS > 0 { 1 } else { 0 };
+        let chunk_size = count.div_ceil(GENERATOR_THREADS);
         for i in 0..GENERATOR_THREADS {
             let start_id = i * chunk_size;
-            let generate_count = if i == GENERATOR_THREADS - 1 {
-                if count - (i * chunk_size) > chunk_size {
-                    chunk_size
-                } else {
-                    count - (i * chunk_size)
-                }
-            } else {
-                chunk_size
-            };
+            let generate_count = chunk_size.min(count.saturating_sub(start_id));
             if generate_count == 0 {
                 continue;
             }
             let tx = tx.clone();
-            let generator = crate::data_generator::DataGenerator::new();
+            let generator = Arc::clone(&generator);
+            let gate = Arc::clone(&gate);
+            let span = info_span!("generator", start_id, count = generate_count);
             generator_handles.push(task::spawn_blocking(move || {
-                generator.generate(generate_count, start_id, tx);
-                // () // Explicitly return () to clarify closure return type
+                let _entered = span.enter();
+                if !gate.failed() {
+                    generator.generate(generate_count, start_id, tx);
+                }
             }));
         }
 
         let pool = self.pool.clone();
         let mut inserter_handles = vec![];
-        for _ in 0..INSERTER_THREADS {
+        for worker in 0..INSERTER_THREADS {
             let rx = Arc::clone(&rx);
             let pool = pool.clone();
+            let generator = Arc::clone(&generator);
+            let job = Arc::clone(&job);
             let limiter = Arc::clone(&limiter);
-
-            inserter_handles.push(task::spawn_blocking(async move || {
-                let mut conn = match pool.get_conn() {
-                    Ok(conn) => conn,
-                    Err(e) => {
-                        eprintln!("Failed to get database connection: {}", e);
-                        return;
-                    }
-                };
-                let tx_opts = TxOpts::default();
-                let mut tx = match conn.start_transaction(tx_opts) {
-                    Ok(tx) => tx,
-                    Err(e) => {
-                        eprintln!("Failed to start transaction: {}", e);
-                        return;
-                    }
-                };
-
-                loop {
-                    let persons = {
-                        let rx = match rx.lock() {
-                            Ok(rx) => rx,
-                            Err(e) => {
-                                eprintln!("Failed to lock receiver: {}", e);
-                                return;
-                            }
-                        };
+            let gate = Arc::clone(&gate);
+            let handle = Handle::current();
+
+            let span = info_span!("inserter", worker);
+            inserter_handles.push(task::spawn_blocking(move || -> Result<(), String> {
+                let _entered = span.enter();
+                let arrival = gate.join();
+                let mut conn = metrics::global()
+                    .get_conn(&pool)
+                    .map_err(|e| format!("Failed to get database connection: {}", e))?;
+                let mut tx = conn
+                    .start_transaction(TxOpts::default())
+                    .map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+                // Rows written in `tx`; they only count as inserted once it
+                // commits.
+                let mut pending = 0;
+                while !gate.failed() {
+                    let rows = {
+                        let rx = rx
+                            .lock()
+                            .map_err(|e| format!("Failed to lock receiver: {}", e))?;
                         match rx.recv() {
-                            Ok(persons) => persons,
+                            Ok(rows) => rows,
                             Err(_) => break, // Channel closed
                         }
                     };
-
-                    for person in persons.chunks(BATCH_SIZE as usize)  {
-
-                        limiter.until_ready().await;
-
-                        let params: Vec<_> = person.iter().map(|p| {
-                        params! {
-                                "name" => &p.name,
-                                "email" => &p.email,
-                                "phone" => &p.phone,
-                                "address" => &p.address,
-                                "city" => &p.city,
-                                "state" => &p.state,
-                                "version" => p.version,
+                    job.generated(rows.len());
+
+                    for batch in rows.chunks(BATCH_SIZE as usize) {
+                        // Costs above the burst size are paid in burst-sized installments.
+                        let mut remaining = batch_cost(unit, batch);
+                        while remaining > 0 {
+                            let n = remaining.min(quota.burst_size().get() as u64) as u32;
+                            if let Some(n) = NonZeroU32::new(n)
+                                && let Err(e) = handle.block_on(limiter.until_n_ready(n))
+                            {
+                                job.failed(pending);
+                                return Err(format!("Rate limiter rejected batch: {}", e));
                             }
-                        }).collect();
-                        if let Err(e) = tx.exec_batch(
-                        "INSERT INTO person (name, email, phone, address, city, state, version) VALUES (:name, :email, :phone, :address, :city, :state, :version)",
-                        params.iter(),
-                    ) {
-                        eprintln!("Failed to execute batch insert: {}", e);
-                        return;
-                    }
-                        println!("Inserted {} records", person.len());
-                    }                   
+                            remaining -= n as u64;
+                        }
 
-                    
+                        if let Err(e) =
+                            strategy.insert(&mut tx, generator.table(), generator.columns(), batch)
+                        {
+                            job.failed(pending + batch.len());
+                            return Err(format!("Failed to execute batch insert: {}", e));
+                        }
+                        pending += batch.len();
+                        debug!("Inserted {} records", batch.len());
+                    }
                 }
 
+                if !arrival.may_commit() {
+                    warn!(
+                        "Rolling back {} rows after another inserter failed",
+                        pending
+                    );
+                    job.failed(pending);
+                    return Ok(());
+                }
                 if let Err(e) = tx.commit() {
-                    eprintln!("Failed to commit transaction: {}", e);
+                    job.failed(pending);
+                    return Err(format!("Failed to commit transaction: {}", e));
                 }
+                job.inserted(pending);
+                Ok(())
             }));
         }
 
         // Wait for generators to finish
         for handle in generator_handles {
             if let Err(e) = handle.await {
-                eprintln!("Generator task failed: {:?}", e);
+                error!("Generator task failed: {:?}", e);
             }
         }
 
         // Drop the original sender to close the channel
         drop(tx);
 
-        // Wait for inserters to finish
+        // Wait for inserters to finish; the job failed if any of them did.
+        let mut failure = None;
         for handle in inserter_handles {
-            if let Err(e) = handle.await {
-                eprintln!("Inserter task failed: {:?}", e);
+            let result = handle
+                .await
+                .unwrap_or_else(|e| Err(format!("Inserter task failed: {}", e)));
+            if let Err(e) = result {
+                error!("{}", e);
+                failure.get_or_insert(e);
             }
         }
 
-        Ok(start_time.elapsed())
+        match failure {
+            Some(e) => Err(e),
+            None => Ok(start_time.elapsed()),
+        }
     }
 }
@@ -0,0 +1,83 @@
+use crate::row_generator::{Row, RowGenerator};
+use crate::sink::RowSink;
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::time::{Duration, Instant};
+use tracing::error;
+
+type Batch = (u32, Vec<Row>);
+
+/// Runs the generators into a `RowSink` instead of the database.
+pub struct DataWriter {
+    sink: Box<dyn RowSink>,
+}
+
+impl DataWriter {
+    pub fn new(sink: Box<dyn RowSink>) -> Self {
+        DataWriter { sink }
+    }
+
+    pub fn write(self, generator: Arc<dyn RowGenerator>, count: u32) -> io::Result<Duration> {
+        let start_time = Instant::now();
+        const BATCH_SIZE: u32 = 1000;
+        const GENERATOR_THREADS: u32 = 4;
+
+        // Bounded so generators cannot run far ahead of the file.
+        let (tx, rx): (SyncSender<Batch>, Receiver<Batch>) =
+            std::sync::mpsc::sync_channel(GENERATOR_THREADS as usize * 2);
+        let batches = count.div_ceil(BATCH_SIZE);
+        let mut generator_handles = vec![];
+
+        // Batches are dealt round-robin so every thread works near the front
+        // of the file and few out-of-order batches pile up below.
+        for i in 0..GENERATOR_THREADS.min(batches) {
+            let tx = tx.clone();
+            let generator = Arc::clone(&generator);
+            generator_handles.push(std::thread::spawn(move || {
+                for batch in (i..batches).step_by(GENERATOR_THREADS as usize) {
+                    let start = batch * BATCH_SIZE;
+                    let end = (start + BATCH_SIZE).min(count);
+                    let rows = (start..end)
+                        .map(|index| generator.row(index as u64))
+                        .collect();
+                    if tx.send((batch, rows)).is_err() {
+                        return; // Writer gave up
+                    }
+                }
+            }));
+        }
+        drop(tx);
+
+        let mut sink = self.sink;
+        let mut pending = BTreeMap::new();
+        let mut next_batch = 0;
+        let mut result = Ok(());
+        for (batch, rows) in rx.iter() {
+            pending.insert(batch, rows);
+            while let Some(rows) = pending.remove(&next_batch) {
+                result = sink.write_rows(&rows);
+                if result.is_err() {
+                    break;
+                }
+                next_batch += 1;
+            }
+            if result.is_err() {
+                break;
+            }
+        }
+        // Dropping the receiver unblocks any generator still sending.
+        drop(rx);
+
+        for handle in generator_handles {
+            if let Err(e) = handle.join() {
+                error!("Generator thread failed: {:?}", e);
+            }
+        }
+
+        result?;
+        sink.finish()?;
+        Ok(start_time.elapsed())
+    }
+}
@@ -0,0 +1,189 @@
+use crate::metrics;
+use crate::row_generator::Row;
+use crate::sink::{self, OutputFormat, RowSink};
+use mysql::prelude::*;
+use mysql::{Pool, PooledConn};
+use std::io::Write;
+
+/// Rows read per query. Each page is read in full before any of it is
+/// written, so a slow reader never holds a result set open on the server.
+const PAGE_SIZE: usize = 1000;
+
+/// Columns of `person` in the order exports list them, primary key first.
+pub const PERSON_COLUMNS: &[&str] = &[
+    "id",
+    "name",
+    "email",
+    "phone",
+    "address",
+    "city",
+    "state",
+    "version",
+    "updated_by",
+];
+
+/// Where and how `export` writes a table.
+pub struct ExportArgs {
+    pub table: String,
+    pub output: String,
+    pub format: OutputFormat,
+    pub gzip: bool,
+}
+
+/// Writes every row of `args.table` to the output file and returns how
+/// many were written. The table needs a single-column primary key.
+pub fn export(pool: &Pool, args: &ExportArgs) -> Result<u64, String> {
+    check_table_name(&args.table)?;
+    let mut conn = metrics::global()
+        .get_conn(pool)
+        .map_err(|e| format!("Failed to connect: {}", e))?;
+    let (columns, key) = table_columns(&mut conn, &args.table)?;
+    let sink = sink::open_file_sink(&args.output, args.format, args.gzip, &args.table, &columns)
+        .map_err(|e| format!("Failed to open {}: {}", args.output, e))?;
+    export_rows(&mut conn, &args.table, &columns, &key, sink, &args.output)
+}
+
+/// Writes `PERSON_COLUMNS` of every person to `writer`, for
+/// `GET /persons/export`.
+pub fn export_persons(
+    conn: &mut PooledConn,
+    format: OutputFormat,
+    writer: &mut dyn Write,
+) -> Result<u64, String> {
+    let columns: Vec<String> = PERSON_COLUMNS.iter().map(|c| c.to_string()).collect();
+    let sink = sink::new_sink(writer, format, "person", &columns).map_err(|e| e.to_string())?;
+    export_rows(conn, "person", &columns, "id", sink, "the response")
+}
+
+fn check_table_name(table: &str) -> Result<(), String> {
+    if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Err(format!("Invalid table name: {}", table));
+    }
+    Ok(())
+}
+
+/// Columns of `table` in table order, and its primary key column.
+fn table_columns(conn: &mut PooledConn, table: &str) -> Result<(Vec<String>, String), String> {
+    let read_error = |e: mysql::Error| format!("Failed to read the columns of {}: {}", table, e);
+    let columns: Vec<String> = conn
+        .exec(
+            "SELECT COLUMN_NAME FROM information_schema.COLUMNS \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? ORDER BY ORDINAL_POSITION",
+            (table,),
+        )
+        .map_err(read_error)?;
+    if columns.is_empty() {
+        return Err(format!("No table named {}", table));
+    }
+    let key: Vec<String> = conn
+        .exec(
+            "SELECT COLUMN_NAME FROM information_schema.KEY_COLUMN_USAGE \
+             WHERE TABLE_SCHEMA = DATABASE() AND TABLE_NAME = ? AND CONSTRAINT_NAME = 'PRIMARY'",
+            (table,),
+        )
+        .map_err(read_error)?;
+    match <[String; 1]>::try_from(key) {
+        Ok([key]) => Ok((columns, key)),
+        Err(_) => Err(format!(
+            "{} needs a single-column primary key to be exported",
+            table
+        )),
+    }
+}
+
+/// `SELECT` of the page after the row whose `key` is bound as the only
+/// parameter, or of the first page.
+fn page_query(table: &str, columns: &[String], key: &str, first: bool) -> String {
+    let columns = columns
+        .iter()
+        .map(|column| format!("`{}`", column))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let after = if first {
+        String::new()
+    } else {
+        format!(" WHERE `{}` > ?", key)
+    };
+    format!(
+        "SELECT {} FROM `{}`{} ORDER BY `{}` LIMIT {}",
+        columns, table, after, key, PAGE_SIZE
+    )
+}
+
+/// Reads `table` a page at a time in `key` order, so only one page is held
+/// in memory however large the table, and writes it to `sink`.
+/// `destination` names the sink in errors.
+fn export_rows(
+    conn: &mut PooledConn,
+    table: &str,
+    columns: &[String],
+    key: &str,
+    mut sink: Box<dyn RowSink + '_>,
+    destination: &str,
+) -> Result<u64, String> {
+    let read_error = |e: mysql::Error| format!("Failed to read {}: {}", table, e);
+    let write_error = |e| format!("Failed to write {}: {}", destination, e);
+    let key_index = columns
+        .iter()
+        .position(|column| column == key)
+        .ok_or_else(|| format!("{} is not among the exported columns", key))?;
+
+    let mut count = 0;
+    let mut last: Option<mysql::Value> = None;
+    loop {
+        // The binary protocol keeps numbers typed in NDJSON output.
+        let page: Vec<Row> = match last.take() {
+            None => conn.exec(page_query(table, columns, key, true), ()),
+            Some(last) => conn.exec(page_query(table, columns, key, false), (last,)),
+        }
+        .map_err(read_error)?
+        .into_iter()
+        .map(mysql::Row::unwrap)
+        .collect();
+        if page.is_empty() {
+            break;
+        }
+        sink.write_rows(&page).map_err(write_error)?;
+        count += page.len() as u64;
+        if page.len() < PAGE_SIZE {
+            break;
+        }
+        last = Some(page[page.len() - 1][key_index].clone());
+    }
+    sink.finish().map_err(write_error)?;
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns() -> Vec<String> {
+        PERSON_COLUMNS.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn pages_list_their_columns_in_key_order() {
+        assert_eq!(
+            page_query("person", &columns()[..3], "id", true),
+            "SELECT `id`, `name`, `email` FROM `person` ORDER BY `id` LIMIT 1000"
+        );
+    }
+
+    #[test]
+    fn later_pages_start_after_the_last_key() {
+        assert_eq!(
+            page_query("orders", &columns()[..1], "id", false),
+            "SELECT `id` FROM `orders` WHERE `id` > ? ORDER BY `id` LIMIT 1000"
+        );
+    }
+
+    #[test]
+    fn table_names_are_checked_before_they_reach_sql() {
+        assert!(check_table_name("person").is_ok());
+        assert!(check_table_name("order_items2").is_ok());
+        for table in ["", "person`; DROP TABLE person", "a.b", "a-b"] {
+            assert!(check_table_name(table).is_err(), "{}", table);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::sync::LazyLock;
+
+static FIRST_NAMES: LazyLock<Vec<&str>> = LazyLock::new(|| lines(include_str!("first_names.txt")));
+static LAST_NAMES: LazyLock<Vec<&str>> = LazyLock::new(|| lines(include_str!("last_names.txt")));
+static STREETS: LazyLock<Vec<&str>> = LazyLock::new(|| lines(include_str!("streets.txt")));
+static STREET_SUFFIXES: LazyLock<Vec<&str>> =
+    LazyLock::new(|| lines(include_str!("street_suffixes.txt")));
+static EMAIL_DOMAINS: LazyLock<Vec<&str>> =
+    LazyLock::new(|| lines(include_str!("email_domains.txt")));
+// Each line is `city,STATE` so a city is always paired with its own state.
+static CITIES: LazyLock<Vec<(&str, &str)>> = LazyLock::new(|| {
+    lines(include_str!("cities.txt"))
+        .into_iter()
+        .filter_map(|line| line.rsplit_once(','))
+        .collect()
+});
+
+fn lines(content: &str) -> Vec<&str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+/// Small SplitMix64 generator. Its output is fixed for a given seed, unlike
+/// external RNGs whose streams may change between releases.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    /// Independent stream for row `index` of a run, so a row's values do not
+    /// depend on how the rows were split across generator threads.
+    pub fn for_row(seed: u64, index: u64) -> Self {
+        let mut rng = Rng(seed ^ index.wrapping_mul(0xD1B5_4A32_D192_ED03));
+        rng.next_u64();
+        rng
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform value in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+
+    /// Uniform value in `min..=max`.
+    pub fn range(&mut self, min: u64, max: u64) -> u64 {
+        min + self.below(max - min + 1)
+    }
+
+    pub fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+        items[self.below(items.len() as u64) as usize]
+    }
+}
+
+pub fn first_name(rng: &mut Rng) -> &'static str {
+    rng.pick(&FIRST_NAMES)
+}
+
+pub fn last_name(rng: &mut Rng) -> &'static str {
+    rng.pick(&LAST_NAMES)
+}
+
+/// Email built from the person's name; `unique` is appended to the local part
+/// so rows never collide.
+pub fn email(rng: &mut Rng, first: &str, last: &str, unique: u64) -> String {
+    format!(
+        "{}.{}{}@{}",
+        first.to_lowercase(),
+        last.to_lowercase(),
+        unique,
+        rng.pick(&EMAIL_DOMAINS)
+    )
+}
+
+// Area codes and exchanges never start with 0 or 1 and skip the reserved
+// N11 service codes.
+fn nanp_code(rng: &mut Rng) -> u64 {
+    loop {
+        let code = rng.range(200, 999);
+        if code % 100 != 11 {
+            return code;
+        }
+    }
+}
+
+/// North American number formatted as `(555) 555-0123`.
+pub fn phone(rng: &mut Rng) -> String {
+    let area = nanp_code(rng);
+    let exchange = nanp_code(rng);
+    format!("({}) {}-{:04}", area, exchange, rng.below(10000))
+}
+
+pub fn street_address(rng: &mut Rng) -> String {
+    format!(
+        "{} {} {}",
+        rng.range(1, 9999),
+        rng.pick(&STREETS),
+        rng.pick(&STREET_SUFFIXES)
+    )
+}
+
+/// City together with its two-letter US state code.
+pub fn city_and_state(rng: &mut Rng) -> (&'static str, &'static str) {
+    rng.pick(&CITIES)
+}
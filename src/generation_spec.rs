@@ -0,0 +1,490 @@
+use crate::data_generator::DataGenerator;
+use crate::fake_data::Rng;
+use crate::row_generator::{Row, RowGenerator};
+use mysql::Value;
+use serde::Deserialize;
+use std::fs;
+use std::sync::Arc;
+
+/// Declarative description of how to fill one or more tables, loaded from TOML:
+///
+/// ```toml
+/// [[table]]
+/// name = "orders"
+///
+/// [[table.columns]]
+/// name = "reference"
+/// kind = "template"
+/// pattern = "ORD-####-??"
+/// unique = true
+/// ```
+#[derive(Deserialize)]
+pub struct GenerationSpec {
+    #[serde(rename = "table", default)]
+    pub tables: Vec<TableSpec>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct TableSpec {
+    pub name: String,
+    pub columns: Vec<ColumnSpec>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ColumnSpec {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: ColumnKind,
+    /// Fraction of rows, between 0 and 1, that get `NULL` in this column.
+    #[serde(default)]
+    pub null_ratio: f64,
+    #[serde(default)]
+    pub unique: bool,
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ColumnKind {
+    /// `start`, `start + step`, `start + 2 * step`, ...
+    Sequence {
+        #[serde(default = "default_one")]
+        start: i64,
+        #[serde(default = "default_one")]
+        step: i64,
+    },
+    /// One of a fixed list of values.
+    Enum { values: Vec<String> },
+    /// String built from `pattern`: `#` is a digit, `?` an uppercase letter,
+    /// `*` a lowercase letter or digit, `[abc]`/`[a-z]` a character class and
+    /// `\` escapes the next character. Anything else is copied as is.
+    Template { pattern: String },
+    /// Number between `min` and `max` inclusive; integers unless `decimals` is set.
+    Range {
+        min: f64,
+        max: f64,
+        #[serde(default)]
+        decimals: u32,
+    },
+}
+
+fn default_one() -> i64 {
+    1
+}
+
+impl GenerationSpec {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read generation spec {}: {}", path, e))?;
+        let spec: GenerationSpec = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse generation spec {}: {}", path, e))?;
+        for table in &spec.tables {
+            table.validate()?;
+        }
+        Ok(spec)
+    }
+
+    pub fn table(&self, name: &str) -> Option<&TableSpec> {
+        self.tables.iter().find(|table| table.name == name)
+    }
+}
+
+/// Generator for `table`: its entry in the spec file if there is one,
+/// otherwise the built-in person generator for `person`.
+pub fn generator_for(
+    spec_file: Option<&str>,
+    table: &str,
+    seed: u64,
+) -> Result<Arc<dyn RowGenerator>, String> {
+    let spec = match spec_file {
+        Some(path) => Some(GenerationSpec::load(path)?),
+        None => None,
+    };
+    match spec.as_ref().and_then(|spec| spec.table(table)) {
+        Some(table_spec) => Ok(Arc::new(SpecGenerator::new(table_spec, seed))),
+        None if table == "person" => Ok(Arc::new(DataGenerator::new(seed))),
+        None => Err(format!("No generation spec for table {}", table)),
+    }
+}
+
+fn valid_identifier(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl TableSpec {
+    fn validate(&self) -> Result<(), String> {
+        if !valid_identifier(&self.name) {
+            return Err(format!("Invalid table name: {:?}", self.name));
+        }
+        if self.columns.is_empty() {
+            return Err(format!("Table {} has no columns", self.name));
+        }
+        for column in &self.columns {
+            let context = format!("{}.{}", self.name, column.name);
+            if !valid_identifier(&column.name) {
+                return Err(format!("Invalid column name: {:?}", context));
+            }
+            if !(0.0..=1.0).contains(&column.null_ratio) {
+                return Err(format!("{}: null_ratio must be between 0 and 1", context));
+            }
+            match &column.kind {
+                ColumnKind::Sequence { step, .. } if *step == 0 => {
+                    return Err(format!("{}: step must not be 0", context));
+                }
+                ColumnKind::Enum { values } if values.is_empty() => {
+                    return Err(format!("{}: values must not be empty", context));
+                }
+                ColumnKind::Template { pattern } => {
+                    parse_template(pattern).map_err(|e| format!("{}: {}", context, e))?;
+                }
+                ColumnKind::Range { min, max, .. } if min > max => {
+                    return Err(format!("{}: min must not exceed max", context));
+                }
+                _ => {}
+            }
+            // Unique values come from a permutation of the value space, which
+            // has to fit in a u64.
+            if column.unique
+                && !matches!(column.kind, ColumnKind::Sequence { .. })
+                && ColumnGenerator::new(&column.kind).space().is_none()
+            {
+                return Err(format!(
+                    "{}: too many possible values to keep unique",
+                    context
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+enum TemplatePart {
+    Literal(char),
+    Class(Vec<char>),
+}
+
+fn parse_template(pattern: &str) -> Result<Vec<TemplatePart>, String> {
+    let mut parts = Vec::new();
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        let part = match c {
+            '#' => TemplatePart::Class(('0'..='9').collect()),
+            '?' => TemplatePart::Class(('A'..='Z').collect()),
+            '*' => TemplatePart::Class(('a'..='z').chain('0'..='9').collect()),
+            '\\' => match chars.next() {
+                Some(escaped) => TemplatePart::Literal(escaped),
+                None => return Err("pattern ends with an escape".to_string()),
+            },
+            '[' => {
+                let mut class = Vec::new();
+                let mut previous = None;
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some('-') if previous.is_some() => {
+                            let end = chars
+                                .next()
+                                .filter(|end| *end != ']')
+                                .ok_or("unterminated range in character class")?;
+                            let start = class.pop().unwrap_or(end);
+                            if start > end {
+                                return Err(format!("invalid range {}-{}", start, end));
+                            }
+                            class.extend(start..=end);
+                            previous = None;
+                        }
+                        Some(member) => {
+                            class.push(member);
+                            previous = Some(member);
+                        }
+                        None => return Err("unterminated character class".to_string()),
+                    }
+                }
+                class.sort_unstable();
+                class.dedup();
+                if class.is_empty() {
+                    return Err("empty character class".to_string());
+                }
+                TemplatePart::Class(class)
+            }
+            _ => TemplatePart::Literal(c),
+        };
+        parts.push(part);
+    }
+    Ok(parts)
+}
+
+/// Seeded bijection on `0..space`, so unique columns can map row `index` to
+/// a distinct value without remembering what was handed out. Mixes within the
+/// next power of two and cycle-walks until the result falls inside `space`.
+struct Permutation {
+    space: u64,
+    mask: u64,
+    shift: u32,
+    keys: [(u64, u64); 3],
+}
+
+impl Permutation {
+    fn new(space: u64, seed: u64) -> Self {
+        let bits = (64 - space.saturating_sub(1).leading_zeros()).max(1);
+        let mut rng = Rng::new(seed);
+        Permutation {
+            space,
+            mask: u64::MAX >> (64 - bits),
+            shift: bits.div_ceil(2),
+            // Odd multipliers keep each round invertible modulo 2^bits.
+            keys: [(); 3].map(|_| (rng.next_u64() | 1, rng.next_u64())),
+        }
+    }
+
+    fn apply(&self, index: u64) -> u64 {
+        let mut x = index;
+        loop {
+            for (multiplier, offset) in self.keys {
+                x = x.wrapping_mul(multiplier).wrapping_add(offset) & self.mask;
+                x ^= x >> self.shift;
+            }
+            if x < self.space {
+                return x;
+            }
+        }
+    }
+}
+
+enum ColumnGenerator {
+    Sequence { start: i64, step: i64 },
+    Enum { values: Vec<String> },
+    Template { parts: Vec<TemplatePart> },
+    Range { min: f64, max: f64, decimals: u32 },
+}
+
+impl ColumnGenerator {
+    fn new(kind: &ColumnKind) -> Self {
+        match kind {
+            ColumnKind::Sequence { start, step } => ColumnGenerator::Sequence {
+                start: *start,
+                step: *step,
+            },
+            ColumnKind::Enum { values } => ColumnGenerator::Enum {
+                values: values.clone(),
+            },
+            ColumnKind::Template { pattern } => ColumnGenerator::Template {
+                // Validated when the spec was loaded.
+                parts: parse_template(pattern).unwrap_or_default(),
+            },
+            ColumnKind::Range { min, max, decimals } => ColumnGenerator::Range {
+                min: *min,
+                max: *max,
+                decimals: *decimals,
+            },
+        }
+    }
+
+    /// Number of distinct values the column can take; `None` if effectively unbounded.
+    fn space(&self) -> Option<u64> {
+        match self {
+            ColumnGenerator::Sequence { .. } => None,
+            ColumnGenerator::Enum { values } => Some(values.len() as u64),
+            ColumnGenerator::Template { parts } => {
+                parts.iter().try_fold(1u64, |space, part| match part {
+                    TemplatePart::Literal(_) => Some(space),
+                    TemplatePart::Class(class) => space.checked_mul(class.len() as u64),
+                })
+            }
+            ColumnGenerator::Range { min, max, decimals } => {
+                let scale = 10f64.powi(*decimals as i32);
+                let steps = ((max - min) * scale).floor();
+                if steps >= u64::MAX as f64 {
+                    None
+                } else {
+                    Some(steps as u64 + 1)
+                }
+            }
+        }
+    }
+
+    /// Value number `n` out of `space()`, for unique columns.
+    fn nth(&self, index: u64, n: u64) -> Value {
+        match self {
+            ColumnGenerator::Sequence { start, step } => {
+                Value::Int(start.wrapping_add(step.wrapping_mul(index as i64)))
+            }
+            ColumnGenerator::Enum { values } => Value::from(&values[n as usize]),
+            ColumnGenerator::Template { parts } => {
+                let mut rest = n;
+                let mut value = String::new();
+                for part in parts.iter().rev() {
+                    match part {
+                        TemplatePart::Literal(c) => value.push(*c),
+                        TemplatePart::Class(class) => {
+                            value.push(class[(rest % class.len() as u64) as usize]);
+                            rest /= class.len() as u64;
+                        }
+                    }
+                }
+                Value::from(value.chars().rev().collect::<String>())
+            }
+            ColumnGenerator::Range { min, decimals, .. } => {
+                let scale = 10f64.powi(*decimals as i32);
+                number(min + n as f64 / scale, *decimals)
+            }
+        }
+    }
+
+    fn random(&self, index: u64, rng: &mut Rng) -> Value {
+        match self {
+            ColumnGenerator::Sequence { .. } => self.nth(index, index),
+            ColumnGenerator::Enum { values } => {
+                Value::from(&values[rng.below(values.len() as u64) as usize])
+            }
+            ColumnGenerator::Template { parts } => {
+                let value: String = parts
+                    .iter()
+                    .map(|part| match part {
+                        TemplatePart::Literal(c) => *c,
+                        TemplatePart::Class(class) => rng.pick(class),
+                    })
+                    .collect();
+                Value::from(value)
+            }
+            ColumnGenerator::Range { min, max, decimals } => match self.space() {
+                Some(space) => self.nth(index, rng.below(space)),
+                None => {
+                    let unit = (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+                    number(min + (max - min) * unit, *decimals)
+                }
+            },
+        }
+    }
+}
+
+fn number(value: f64, decimals: u32) -> Value {
+    if decimals == 0 {
+        Value::Int(value.round() as i64)
+    } else {
+        let scale = 10f64.powi(decimals as i32);
+        Value::Double((value * scale).round() / scale)
+    }
+}
+
+struct Column {
+    generator: ColumnGenerator,
+    null_ratio: f64,
+    permutation: Option<Permutation>,
+}
+
+/// `RowGenerator` driven by a `TableSpec`.
+pub struct SpecGenerator {
+    table: String,
+    names: Vec<String>,
+    columns: Vec<Column>,
+    seed: u64,
+}
+
+impl SpecGenerator {
+    pub fn new(spec: &TableSpec, seed: u64) -> Self {
+        let columns = spec
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                let generator = ColumnGenerator::new(&column.kind);
+                // Sequences are unique on their own; other unique columns walk a
+                // seeded permutation of their value space instead of retrying.
+                let permutation = match (column.unique, generator.space()) {
+                    (true, Some(space)) => Some(Permutation::new(
+                        space,
+                        seed ^ (i as u64).wrapping_mul(0x9E37_79B9),
+                    )),
+                    _ => None,
+                };
+                Column {
+                    generator,
+                    null_ratio: column.null_ratio,
+                    permutation,
+                }
+            })
+            .collect();
+        SpecGenerator {
+            table: spec.name.clone(),
+            names: spec.columns.iter().map(|c| c.name.clone()).collect(),
+            columns,
+            seed,
+        }
+    }
+}
+
+impl RowGenerator for SpecGenerator {
+    fn table(&self) -> &str {
+        &self.table
+    }
+
+    fn columns(&self) -> &[String] {
+        &self.names
+    }
+
+    fn row(&self, index: u64) -> Row {
+        let mut rng = Rng::for_row(self.seed, index);
+        self.columns
+            .iter()
+            .map(|column| {
+                let is_null = column.null_ratio > 0.0
+                    && (rng.next_u64() >> 11) as f64 / ((1u64 << 53) as f64) < column.null_ratio;
+                if is_null {
+                    Value::NULL
+                } else {
+                    match &column.permutation {
+                        Some(permutation) => column.generator.nth(index, permutation.apply(index)),
+                        None => column.generator.random(index, &mut rng),
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn capacity(&self) -> Option<u64> {
+        self.columns
+            .iter()
+            .filter_map(|column| column.permutation.as_ref().map(|p| p.space))
+            .min()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn permutations_are_bijections_on_their_space() {
+        for space in [1, 2, 3, 10, 64, 1000, 4097] {
+            let permutation = Permutation::new(space, 42);
+            let values: HashSet<u64> = (0..space).map(|i| permutation.apply(i)).collect();
+            assert_eq!(values.len() as u64, space);
+            assert!(values.iter().all(|&value| value < space));
+        }
+    }
+
+    #[test]
+    fn permutations_follow_the_seed() {
+        let order = |seed| {
+            let permutation = Permutation::new(1000, seed);
+            (0..20).map(|i| permutation.apply(i)).collect::<Vec<_>>()
+        };
+        assert_eq!(order(1), order(1));
+        assert_ne!(order(1), order(2));
+        assert_ne!(order(1), (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn template_spaces_multiply_their_classes() {
+        let space = |pattern: &str| {
+            ColumnGenerator::new(&ColumnKind::Template {
+                pattern: pattern.to_string(),
+            })
+            .space()
+        };
+        assert_eq!(space("ORD-[A-F]###"), Some(6000));
+        assert_eq!(space(r"\#?"), Some(26));
+        assert_eq!(space(&"*".repeat(20)), None);
+    }
+}
@@ -0,0 +1,143 @@
+use crate::auth::Authenticator;
+use crate::config::{ClientKey, HttpRateLimitConfig};
+use crate::request::Request;
+use crate::response::Response;
+use governor::clock::Clock;
+use governor::middleware::StateInformationMiddleware;
+use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+type KeyedLimiter = DefaultKeyedRateLimiter<String, StateInformationMiddleware>;
+
+// How many checks go by between sweeps of idle client state.
+const CLEANUP_INTERVAL: u64 = 4096;
+
+/// Per-client quotas for the HTTP API, one keyed limiter per configured route
+/// plus one for everything else.
+pub struct HttpRateLimiter {
+    config: HttpRateLimitConfig,
+    routes: Vec<KeyedLimiter>,
+    default: KeyedLimiter,
+    checks: AtomicU64,
+}
+
+/// Outcome of `HttpRateLimiter::check`, carrying what the `RateLimit-*`
+/// headers report.
+pub enum RateLimitDecision {
+    Unlimited,
+    Allowed {
+        limit: u32,
+        remaining: u32,
+        reset: Duration,
+    },
+    Limited {
+        limit: u32,
+        retry_after: Duration,
+    },
+}
+
+fn keyed(quota: Quota) -> KeyedLimiter {
+    RateLimiter::keyed(quota).with_middleware::<StateInformationMiddleware>()
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+impl HttpRateLimiter {
+    pub fn new(config: &HttpRateLimitConfig) -> Self {
+        HttpRateLimiter {
+            config: config.clone(),
+            routes: config
+                .routes
+                .iter()
+                .map(|route| keyed(route.quota()))
+                .collect(),
+            default: keyed(config.quota()),
+            checks: AtomicU64::new(0),
+        }
+    }
+
+    /// Only keys that `authenticator` verifies get a bucket of their own, so
+    /// a client cannot escape its IP's quota by making keys up.
+    fn client_key(&self, request: &Request, authenticator: &Authenticator) -> String {
+        let verified = match self.config.key {
+            ClientKey::Ip => None,
+            ClientKey::ApiKey => authenticator.api_key_name(request),
+        };
+        match (verified, request.peer_addr) {
+            (Some(name), _) => format!("key:{}", name),
+            (None, Some(ip)) => ip.to_string(),
+            (None, None) => "unknown".to_string(),
+        }
+    }
+
+    pub fn check(&self, request: &Request, authenticator: &Authenticator) -> RateLimitDecision {
+        if !self.config.enabled {
+            return RateLimitDecision::Unlimited;
+        }
+        let path = request.path_without_query();
+        let limiter = self
+            .config
+            .routes
+            .iter()
+            .position(|route| route.matches(&request.method, path))
+            .map_or(&self.default, |i| &self.routes[i]);
+
+        if self
+            .checks
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(CLEANUP_INTERVAL)
+        {
+            for limiter in self.routes.iter().chain([&self.default]) {
+                limiter.retain_recent();
+                limiter.shrink_to_fit();
+            }
+        }
+
+        match limiter.check_key(&self.client_key(request, authenticator)) {
+            Ok(snapshot) => {
+                let quota = snapshot.quota();
+                let limit = quota.burst_size().get();
+                let remaining = snapshot.remaining_burst_capacity();
+                RateLimitDecision::Allowed {
+                    limit,
+                    remaining,
+                    reset: quota.replenish_interval() * (limit - remaining),
+                }
+            }
+            Err(not_until) => RateLimitDecision::Limited {
+                limit: not_until.quota().burst_size().get(),
+                retry_after: not_until.wait_time_from(limiter.clock().now()),
+            },
+        }
+    }
+}
+
+impl RateLimitDecision {
+    /// Adds the `RateLimit-*` headers to a response that was let through.
+    pub fn apply(&self, response: Response) -> Response {
+        match self {
+            RateLimitDecision::Allowed {
+                limit,
+                remaining,
+                reset,
+            } => response
+                .with_header("RateLimit-Limit", limit)
+                .with_header("RateLimit-Remaining", remaining)
+                .with_header("RateLimit-Reset", ceil_secs(*reset)),
+            _ => response,
+        }
+    }
+
+    /// 429 response for a client that is over its quota.
+    pub fn too_many_requests(limit: u32, retry_after: Duration) -> Response {
+        let seconds = ceil_secs(retry_after).max(1);
+        Response::text(429, "Too Many Requests")
+            .with_header("Retry-After", seconds)
+            .with_header("RateLimit-Limit", limit)
+            .with_header("RateLimit-Remaining", 0)
+            .with_header("RateLimit-Reset", seconds)
+    }
+}
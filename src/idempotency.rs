@@ -0,0 +1,188 @@
+use crate::config::IdempotencyConfig;
+use crate::request::Request;
+use crate::response::Response;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::debug;
+
+/// Longest key accepted.
+const MAX_KEY_LEN: usize = 255;
+
+struct StoredResponse {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+enum State {
+    InFlight,
+    Done(StoredResponse),
+}
+
+struct Entry {
+    /// Hash of the method, path and body the key was first used with.
+    fingerprint: [u8; 32],
+    expires: Instant,
+    state: State,
+}
+
+/// Responses to `POST` requests by their `Idempotency-Key`, kept in memory
+/// for `[idempotency] ttl_secs`. Nothing survives a restart.
+#[derive(Default)]
+pub struct IdempotencyStore {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+/// Drops the in-flight entry of a handler that never returned, so that
+/// retries are not refused until it expires.
+struct Pending<'a> {
+    store: &'a IdempotencyStore,
+    key: &'a str,
+    done: bool,
+}
+
+impl Drop for Pending<'_> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.store.lock().remove(self.key);
+        }
+    }
+}
+
+fn fingerprint(request: &Request) -> [u8; 32] {
+    let mut hash = Sha256::new();
+    for part in [&request.method, &request.path, &request.body] {
+        hash.update(part.as_bytes());
+        hash.update([0]);
+    }
+    hash.finalize().into()
+}
+
+fn conflict(message: &str) -> Response {
+    Response::text(409, message)
+}
+
+impl IdempotencyStore {
+    pub fn new() -> Self {
+        IdempotencyStore::default()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Runs `handle` unless `request` is a `POST` repeating the key of an
+    /// earlier one from the same caller. A repeat of a finished request gets
+    /// its response again; a repeat of one still running, or with another
+    /// method, path or body, gets 409. A keyed request with neither
+    /// credentials nor a client address gets 400. Server errors and streamed
+    /// responses are not kept, so those requests can be retried.
+    pub fn handle(
+        &self,
+        config: &IdempotencyConfig,
+        request: &Request,
+        handle: impl FnOnce() -> Response,
+    ) -> Response {
+        if !config.enabled || !request.method.eq_ignore_ascii_case("POST") {
+            return handle();
+        }
+        let Some(key) = request.header(&config.header) else {
+            return handle();
+        };
+        if key.is_empty() || key.len() > MAX_KEY_LEN || !key.bytes().all(|b| b.is_ascii_graphic()) {
+            return Response::text(
+                400,
+                format!(
+                    "{} must be 1 to {} visible ASCII characters",
+                    config.header, MAX_KEY_LEN
+                ),
+            );
+        }
+        // Keys are per caller, so one client cannot replay another's response.
+        // Without credentials the caller is its address; with neither there
+        // is no caller to scope the key to.
+        let caller = match (&request.principal, request.peer_addr) {
+            (Some(principal), _) if principal.claims.is_some() => {
+                format!("jwt {}", principal.name)
+            }
+            (Some(principal), _) => format!("api-key {}", principal.name),
+            (None, Some(addr)) => format!("address {}", addr),
+            (None, None) => {
+                return Response::text(
+                    400,
+                    format!("{} needs an authenticated caller", config.header),
+                );
+            }
+        };
+        let scoped = format!("{}\n{}", caller, key);
+        let fingerprint = fingerprint(request);
+
+        {
+            let mut entries = self.lock();
+            let now = Instant::now();
+            if let Some(entry) = entries.get(&scoped)
+                && entry.expires > now
+            {
+                if entry.fingerprint != fingerprint {
+                    return conflict("Idempotency key was already used for a different request");
+                }
+                return match &entry.state {
+                    State::InFlight => {
+                        conflict("A request with this idempotency key is still in progress")
+                            .with_header("Retry-After", 1)
+                    }
+                    State::Done(stored) => {
+                        debug!("Replaying the response to idempotency key {:?}", key);
+                        Response {
+                            status: stored.status,
+                            headers: stored.headers.clone(),
+                            body: stored.body.clone(),
+                            stream: None,
+                        }
+                        .with_header("Idempotent-Replayed", "true")
+                    }
+                };
+            }
+            if entries.len() >= config.max_keys {
+                entries.retain(|_, entry| entry.expires > now);
+            }
+            if entries.len() >= config.max_keys
+                && let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.expires)
+                    .map(|(key, _)| key.clone())
+            {
+                entries.remove(&oldest);
+            }
+            entries.insert(
+                scoped.clone(),
+                Entry {
+                    fingerprint,
+                    expires: now + config.ttl(),
+                    state: State::InFlight,
+                },
+            );
+        }
+
+        let mut pending = Pending {
+            store: self,
+            key: &scoped,
+            done: false,
+        };
+        let response = handle();
+        if response.status < 500 && response.stream.is_none() {
+            pending.done = true;
+            if let Some(entry) = self.lock().get_mut(&scoped) {
+                entry.expires = Instant::now() + config.ttl();
+                entry.state = State::Done(StoredResponse {
+                    status: response.status,
+                    headers: response.headers.clone(),
+                    body: response.body.clone(),
+                });
+            }
+        }
+        response
+    }
+}
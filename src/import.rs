@@ -0,0 +1,209 @@
+use crate::config::{PersonField, ValidationConfig};
+use crate::insert_strategy::InsertStrategy;
+use crate::model::person::NewPerson;
+use crate::row_generator::Row;
+use crate::sink::OutputFormat;
+use crate::validation::{self, FieldError};
+use mysql::{PooledConn, TxOpts};
+
+const BATCH_SIZE: usize = 1000;
+/// Per-line errors kept for the report; `rejected` still counts the rest.
+const MAX_REPORTED_ERRORS: usize = 100;
+
+/// Parsed lines of an upload: the line number and the values of
+/// `PersonField::ALL`, or why the line could not be read.
+pub type Records<'a> = Box<dyn Iterator<Item = (u64, Result<Vec<String>, String>)> + 'a>;
+
+/// A line that was not imported.
+pub struct LineError {
+    pub line: u64,
+    pub error: String,
+    /// `[validation]` rules the line broke, if that is why.
+    pub fields: Vec<FieldError>,
+}
+
+/// Outcome of `import`.
+#[derive(Default)]
+pub struct ImportReport {
+    pub accepted: u64,
+    pub rejected: u64,
+    pub errors: Vec<LineError>,
+}
+
+impl ImportReport {
+    fn reject(&mut self, line: u64, error: String, fields: Vec<FieldError>) {
+        self.rejected += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(LineError {
+                line,
+                error,
+                fields,
+            });
+        }
+    }
+}
+
+/// Reads `body` a line at a time. CSV needs a header row naming at least the
+/// `required` columns; NDJSON needs one object per line. Columns that
+/// `person` does not take, such as an export's `id`, are ignored.
+pub fn parse<'a>(
+    body: &'a str,
+    format: OutputFormat,
+    required: &[PersonField],
+) -> Result<Records<'a>, String> {
+    match format {
+        OutputFormat::Csv => csv_records(body, required),
+        OutputFormat::Ndjson => Ok(ndjson_records(body)),
+        OutputFormat::Sql { .. } => Err("SQL dumps cannot be imported".to_string()),
+    }
+}
+
+fn csv_records<'a>(body: &'a str, required: &[PersonField]) -> Result<Records<'a>, String> {
+    let mut reader = csv::ReaderBuilder::new().from_reader(body.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Invalid CSV header: {}", e))?
+        .clone();
+    let positions: Vec<Option<usize>> = PersonField::ALL
+        .iter()
+        .map(|field| {
+            headers
+                .iter()
+                .position(|header| header.trim() == field.as_str())
+        })
+        .collect();
+    for (field, position) in PersonField::ALL.iter().zip(&positions) {
+        if position.is_none() && required.contains(field) {
+            return Err(format!("CSV header has no {} column", field.as_str()));
+        }
+    }
+    Ok(Box::new(reader.into_records().map(move |record| {
+        let line = match &record {
+            Ok(record) => record.position(),
+            Err(e) => e.position(),
+        }
+        .map_or(0, |position| position.line());
+        let values = record.map_err(csv_error).map(|record| {
+            positions
+                .iter()
+                .map(|position| {
+                    position
+                        .and_then(|i| record.get(i))
+                        .unwrap_or_default()
+                        .to_string()
+                })
+                .collect()
+        });
+        (line, values)
+    })))
+}
+
+fn csv_error(e: csv::Error) -> String {
+    match e.kind() {
+        csv::ErrorKind::UnequalLengths {
+            expected_len, len, ..
+        } => format!("Expected {} fields, found {}", expected_len, len),
+        _ => e.to_string(),
+    }
+}
+
+fn ndjson_records(body: &str) -> Records<'_> {
+    Box::new(
+        body.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| (i as u64 + 1, ndjson_values(line))),
+    )
+}
+
+fn ndjson_values(line: &str) -> Result<Vec<String>, String> {
+    let object: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(line).map_err(|e| format!("Invalid JSON object: {}", e))?;
+    PersonField::ALL
+        .iter()
+        .map(|field| match object.get(field.as_str()) {
+            None | Some(serde_json::Value::Null) => Ok(String::new()),
+            Some(serde_json::Value::String(value)) => Ok(value.clone()),
+            Some(_) => Err(format!("{} must be a string", field.as_str())),
+        })
+        .collect()
+}
+
+fn new_person(values: Vec<String>) -> NewPerson {
+    let mut values = values.into_iter();
+    let mut next = || values.next().unwrap_or_default();
+    NewPerson {
+        name: next(),
+        email: next(),
+        phone: next(),
+        address: next(),
+        city: next(),
+        state: next(),
+    }
+}
+
+/// Inserts every valid record in batches of `BATCH_SIZE` with `strategy`, in
+/// one transaction so that a database error leaves nothing behind. Lines
+/// that cannot be read or break `config` are skipped and reported. `actor`
+/// is recorded as `updated_by`.
+pub fn import(
+    conn: &mut PooledConn,
+    records: Records,
+    strategy: InsertStrategy,
+    config: &ValidationConfig,
+    actor: Option<&str>,
+) -> Result<ImportReport, String> {
+    let columns: Vec<String> = PersonField::ALL
+        .iter()
+        .map(|field| field.as_str().to_string())
+        .chain(["updated_by".to_string()])
+        .collect();
+    let mut tx = conn
+        .start_transaction(TxOpts::default())
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut report = ImportReport::default();
+    let mut batch: Vec<Row> = Vec::with_capacity(BATCH_SIZE);
+    let mut last_line = 0;
+
+    for (line, values) in records {
+        last_line = line;
+        let person = match values {
+            Ok(values) => new_person(values),
+            Err(e) => {
+                report.reject(line, e, Vec::new());
+                continue;
+            }
+        };
+        let errors = validation::validate(config, &person);
+        if !errors.is_empty() {
+            report.reject(line, "Invalid person".to_string(), errors);
+            continue;
+        }
+        let row: Row = vec![
+            person.name.into(),
+            person.email.into(),
+            person.phone.into(),
+            person.address.into(),
+            person.city.into(),
+            person.state.into(),
+            actor.into(),
+        ];
+        batch.push(row);
+        if batch.len() == BATCH_SIZE {
+            strategy
+                .insert(&mut tx, "person", &columns, &batch)
+                .map_err(|e| format!("Failed to insert rows up to line {}: {}", line, e))?;
+            report.accepted += batch.len() as u64;
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        strategy
+            .insert(&mut tx, "person", &columns, &batch)
+            .map_err(|e| format!("Failed to insert rows up to line {}: {}", last_line, e))?;
+        report.accepted += batch.len() as u64;
+    }
+    tx.commit()
+        .map_err(|e| format!("Failed to commit import: {}", e))?;
+    Ok(report)
+}
@@ -0,0 +1,211 @@
+use crate::row_generator::Row;
+use mysql::{LocalInfileHandler, Params, Transaction, Value, prelude::*};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+
+// MySQL caps a prepared statement at 65535 placeholders.
+const MAX_PLACEHOLDERS: usize = 65535;
+const DEFAULT_ROWS_PER_STATEMENT: usize = 1000;
+
+/// How a batch of generated rows is written to the database.
+#[derive(Clone, Copy, Debug)]
+pub enum InsertStrategy {
+    /// One prepared `INSERT` executed per row.
+    Prepared,
+    /// `INSERT ... VALUES (...),(...)` with up to `rows_per_statement` rows each.
+    MultiRow { rows_per_statement: usize },
+    /// `LOAD DATA LOCAL INFILE` fed from an in-memory buffer.
+    LoadData,
+}
+
+impl InsertStrategy {
+    pub fn from_params(params: &HashMap<String, String>) -> Result<Self, String> {
+        let rows_per_statement = match params.get("rows_per_statement") {
+            Some(value) => Some(
+                value
+                    .parse::<usize>()
+                    .map_err(|_| "Invalid rows_per_statement parameter".to_string())?,
+            ),
+            None => None,
+        };
+        InsertStrategy::parse(
+            params.get("strategy").map(String::as_str),
+            rows_per_statement,
+        )
+    }
+
+    pub fn parse(name: Option<&str>, rows_per_statement: Option<usize>) -> Result<Self, String> {
+        match name {
+            None | Some("prepared") => Ok(InsertStrategy::Prepared),
+            Some("multi_row") => {
+                let rows_per_statement = rows_per_statement.unwrap_or(DEFAULT_ROWS_PER_STATEMENT);
+                if rows_per_statement == 0 || rows_per_statement > MAX_PLACEHOLDERS {
+                    return Err(format!(
+                        "rows_per_statement must be between 1 and {}",
+                        MAX_PLACEHOLDERS
+                    ));
+                }
+                Ok(InsertStrategy::MultiRow { rows_per_statement })
+            }
+            Some("load_data") => Ok(InsertStrategy::LoadData),
+            Some(other) => Err(format!("Unknown insert strategy: {}", other)),
+        }
+    }
+
+    /// The strategy as it runs for a table of `columns` columns: wide tables
+    /// fit fewer rows under the placeholder limit.
+    pub fn for_columns(self, columns: usize) -> Self {
+        match self {
+            InsertStrategy::MultiRow { rows_per_statement } => InsertStrategy::MultiRow {
+                rows_per_statement: rows_per_statement.min(MAX_PLACEHOLDERS / columns.max(1)),
+            },
+            other => other,
+        }
+    }
+
+    pub fn insert(
+        &self,
+        tx: &mut Transaction,
+        table: &str,
+        columns: &[String],
+        rows: &[Row],
+    ) -> mysql::Result<()> {
+        match self.for_columns(columns.len()) {
+            InsertStrategy::Prepared => insert_prepared(tx, table, columns, rows),
+            InsertStrategy::MultiRow { rows_per_statement } => {
+                insert_multi_row(tx, table, columns, rows, rows_per_statement)
+            }
+            InsertStrategy::LoadData => insert_load_data(tx, table, columns, rows),
+        }
+    }
+}
+
+impl fmt::Display for InsertStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InsertStrategy::Prepared => write!(f, "prepared"),
+            InsertStrategy::MultiRow { rows_per_statement } => {
+                write!(f, "multi_row ({} rows per statement)", rows_per_statement)
+            }
+            InsertStrategy::LoadData => write!(f, "load_data"),
+        }
+    }
+}
+
+fn column_list(columns: &[String]) -> String {
+    columns
+        .iter()
+        .map(|column| format!("`{}`", column))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn insert_statement(table: &str, columns: &[String], rows: usize) -> String {
+    let row = format!("({})", vec!["?"; columns.len()].join(", "));
+    format!(
+        "INSERT INTO `{}` ({}) VALUES {}",
+        table,
+        column_list(columns),
+        vec![row.as_str(); rows].join(", ")
+    )
+}
+
+fn insert_prepared(
+    tx: &mut Transaction,
+    table: &str,
+    columns: &[String],
+    rows: &[Row],
+) -> mysql::Result<()> {
+    tx.exec_batch(
+        insert_statement(table, columns, 1),
+        rows.iter().map(|row| Params::Positional(row.clone())),
+    )
+}
+
+fn insert_multi_row(
+    tx: &mut Transaction,
+    table: &str,
+    columns: &[String],
+    rows: &[Row],
+    rows_per_statement: usize,
+) -> mysql::Result<()> {
+    // Full chunks share one prepared statement; only the tail needs its own.
+    let mut full_statement = None;
+    for chunk in rows.chunks(rows_per_statement) {
+        let values: Vec<Value> = chunk.iter().flatten().cloned().collect();
+
+        if chunk.len() == rows_per_statement {
+            if full_statement.is_none() {
+                full_statement =
+                    Some(tx.prep(insert_statement(table, columns, rows_per_statement))?);
+            }
+            if let Some(statement) = &full_statement {
+                tx.exec_drop(statement, values)?;
+            }
+        } else {
+            tx.exec_drop(insert_statement(table, columns, chunk.len()), values)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_infile_field(buf: &mut Vec<u8>, value: &Value) {
+    let bytes = match value {
+        Value::NULL => {
+            buf.extend_from_slice(b"\\N");
+            return;
+        }
+        Value::Bytes(bytes) => bytes.clone(),
+        Value::Int(n) => n.to_string().into_bytes(),
+        Value::UInt(n) => n.to_string().into_bytes(),
+        Value::Float(n) => n.to_string().into_bytes(),
+        Value::Double(n) => n.to_string().into_bytes(),
+        // Dates and times: reuse the SQL literal without its quotes.
+        other => other.as_sql(true).trim_matches('\'').as_bytes().to_vec(),
+    };
+    for byte in bytes {
+        match byte {
+            b'\\' => buf.extend_from_slice(b"\\\\"),
+            b'\t' => buf.extend_from_slice(b"\\t"),
+            b'\n' => buf.extend_from_slice(b"\\n"),
+            b'\r' => buf.extend_from_slice(b"\\r"),
+            0 => buf.extend_from_slice(b"\\0"),
+            _ => buf.push(byte),
+        }
+    }
+}
+
+fn insert_load_data(
+    tx: &mut Transaction,
+    table: &str,
+    columns: &[String],
+    rows: &[Row],
+) -> mysql::Result<()> {
+    let mut data = Vec::with_capacity(rows.len() * 128);
+    for row in rows {
+        for (i, value) in row.iter().enumerate() {
+            if i > 0 {
+                data.push(b'\t');
+            }
+            write_infile_field(&mut data, value);
+        }
+        data.push(b'\n');
+    }
+
+    let mut data = Some(data);
+    tx.set_local_infile_handler(Some(LocalInfileHandler::new(move |_, stream| {
+        match data.take() {
+            Some(data) => stream.write_all(&data),
+            None => Ok(()),
+        }
+    })));
+    let result = tx.query_drop(format!(
+        "LOAD DATA LOCAL INFILE 'populate' INTO TABLE `{}` \
+         FIELDS TERMINATED BY '\\t' ESCAPED BY '\\\\' LINES TERMINATED BY '\\n' ({})",
+        table,
+        column_list(columns)
+    ));
+    tx.set_local_infile_handler(None);
+    result
+}
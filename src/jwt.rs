@@ -0,0 +1,126 @@
+use crate::config::{JwtConfig, Role};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde_json::{Map, Value};
+use std::fs;
+
+/// The claims of a verified token.
+pub type Claims = Map<String, Value>;
+
+/// Verifies HS256 and RS256 bearer tokens against the keys named by
+/// `[auth.jwt]`.
+pub struct JwtVerifier {
+    config: JwtConfig,
+    hs256: Option<DecodingKey>,
+    rs256: Vec<DecodingKey>,
+    jwks: Vec<(Option<String>, DecodingKey)>,
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read {}: {}", path, e))
+}
+
+impl JwtVerifier {
+    /// Loads every configured key; fails on the first that cannot be read.
+    pub fn new(config: &JwtConfig) -> Result<Self, String> {
+        let hs256 = match &config.hs256_secret_path {
+            Some(path) => {
+                let secret = read(path)?;
+                Some(DecodingKey::from_secret(secret.trim_ascii_end()))
+            }
+            None => None,
+        };
+        let rs256 = config
+            .rs256_key_paths
+            .iter()
+            .map(|path| {
+                DecodingKey::from_rsa_pem(&read(path)?)
+                    .map_err(|e| format!("Invalid RSA public key in {}: {}", path, e))
+            })
+            .collect::<Result<_, _>>()?;
+        let jwks = match &config.jwks_path {
+            Some(path) => {
+                let set: JwkSet = serde_json::from_slice(&read(path)?)
+                    .map_err(|e| format!("Invalid JWKS in {}: {}", path, e))?;
+                set.keys
+                    .iter()
+                    .map(|jwk| {
+                        let key = DecodingKey::from_jwk(jwk)
+                            .map_err(|e| format!("Invalid key in {}: {}", path, e))?;
+                        Ok((jwk.common.key_id.clone(), key))
+                    })
+                    .collect::<Result<_, String>>()?
+            }
+            None => Vec::new(),
+        };
+        Ok(JwtVerifier {
+            config: config.clone(),
+            hs256,
+            rs256,
+            jwks,
+        })
+    }
+
+    /// Checks the signature, `exp`, `nbf` and, when configured, `iss` and
+    /// `aud`, and returns the token's claims.
+    pub fn verify(&self, token: &str) -> Result<Claims, String> {
+        let header = jsonwebtoken::decode_header(token).map_err(|e| e.to_string())?;
+        let keys: Vec<&DecodingKey> = match header.alg {
+            Algorithm::HS256 => self.hs256.iter().collect(),
+            Algorithm::RS256 => match &header.kid {
+                // A kid that names a JWKS key selects it; anything else
+                // tries every RS256 key.
+                Some(kid) if self.jwks.iter().any(|(id, _)| id.as_ref() == Some(kid)) => self
+                    .jwks
+                    .iter()
+                    .filter(|(id, _)| id.as_ref() == Some(kid))
+                    .map(|(_, key)| key)
+                    .collect(),
+                _ => self
+                    .rs256
+                    .iter()
+                    .chain(self.jwks.iter().map(|(_, key)| key))
+                    .collect(),
+            },
+            alg => return Err(format!("Unsupported algorithm {:?}", alg)),
+        };
+        if keys.is_empty() {
+            return Err(format!("No {:?} key configured", header.alg));
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = self.config.leeway_secs;
+        validation.validate_nbf = true;
+        match &self.config.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+        if let Some(issuer) = &self.config.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+
+        let mut error = String::new();
+        for key in keys {
+            match jsonwebtoken::decode::<Claims>(token, key, &validation) {
+                Ok(data) => return Ok(data.claims),
+                Err(e) => error = e.to_string(),
+            }
+        }
+        Err(error)
+    }
+
+    /// Highest role named by `role_claim`, which may be a string or a list;
+    /// `default_role` when the claim is missing.
+    pub fn role(&self, claims: &Claims) -> Role {
+        let names = match claims.get(&self.config.role_claim) {
+            None => return self.config.default_role,
+            Some(Value::Array(names)) => names.clone(),
+            Some(name) => vec![name.clone()],
+        };
+        names
+            .into_iter()
+            .filter_map(|name| serde_json::from_value::<Role>(name).ok())
+            .max()
+            .unwrap_or(Role::Public)
+    }
+}
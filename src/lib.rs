@@ -0,0 +1,79 @@
+//! HTTP API over a MySQL `person` table plus tools that fill databases and
+//! files with synthetic rows.
+//!
+//! - [`server::Server`] serves requests; build one with [`server::Server::builder`],
+//!   add routes through a [`router::Router`] and swap the storage behind
+//!   `/person` by implementing [`store::PersonStore`].
+//! - [`populate::populate`] inserts rows from any [`row_generator::RowGenerator`],
+//!   such as the person generator in [`data_generator`] or the TOML-described
+//!   tables in [`generation_spec`].
+//! - [`data_writer::DataWriter`] and [`sink`] write the same rows to CSV,
+//!   NDJSON or SQL files.
+
+/// Per-request log lines in Common, Combined or JSON format.
+pub mod access_log;
+/// API keys and the role each route needs.
+pub mod auth;
+/// `Accept-Encoding` negotiation and response body compression.
+pub mod compression;
+/// Configuration file, `APP_*` overrides and validation.
+pub mod config;
+/// Hot reload of the configuration for a running `Server`.
+pub mod config_reload;
+/// Cross-origin headers and preflight answers.
+pub mod cors;
+/// Realistic rows for the `person` table.
+pub mod data_generator;
+/// Populate engine on plain threads.
+pub mod data_inserter;
+/// Populate engine on tokio, throttled by `[populate.rate_limit]`.
+pub mod data_inserter_with_tokio;
+/// Writes generated rows to a `RowSink` in index order.
+pub mod data_writer;
+/// Dumps a database table to a file.
+pub mod export;
+/// Seeded random names, addresses and phone numbers.
+pub mod fake_data;
+/// Column specs for tables other than `person`.
+pub mod generation_spec;
+/// Per-client request quotas.
+pub mod http_rate_limit;
+/// Replays responses to `POST` requests retried with an `Idempotency-Key`.
+pub mod idempotency;
+/// Loads uploaded CSV or NDJSON into the `person` table.
+pub mod import;
+/// Ways of writing a batch of rows to MySQL.
+pub mod insert_strategy;
+/// HS256 and RS256 bearer token verification.
+pub mod jwt;
+/// Reloadable `tracing` subscriber setup.
+pub mod logging;
+/// Prometheus counters and histograms for `GET /metrics`.
+pub mod metrics;
+/// Embedded schema migrations.
+pub mod migrate;
+/// Rows of the `person` table.
+pub mod model;
+/// Runs a populate job on either engine.
+pub mod populate;
+/// Reading and parsing HTTP/1.1 requests.
+pub mod request;
+/// HTTP responses, buffered or streamed.
+pub mod response;
+/// Method and path dispatch.
+pub mod router;
+mod routes;
+/// Source of synthetic rows for one table.
+pub mod row_generator;
+/// Listeners, connection handling and reload for the HTTP API.
+pub mod server;
+/// Graceful shutdown on SIGTERM and SIGINT.
+pub mod shutdown;
+/// CSV, NDJSON and SQL file output.
+pub mod sink;
+/// Storage behind the `/person` routes.
+pub mod store;
+/// TLS termination for the HTTPS listener.
+pub mod tls;
+/// Field rules for persons, from `[validation]`.
+pub mod validation;
@@ -0,0 +1,40 @@
+use std::io::{self, IsTerminal};
+use std::sync::OnceLock;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{Registry, reload};
+
+static LEVEL: OnceLock<reload::Handle<LevelFilter, Registry>> = OnceLock::new();
+
+/// Installs the global subscriber, writing to stderr with the fields of the
+/// enclosing spans (such as the request id) on every line.
+pub fn init(level: &str) -> Result<(), String> {
+    let (filter, handle) = reload::Layer::new(parse_level(level)?);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(io::stderr)
+                .with_ansi(io::stderr().is_terminal()),
+        )
+        .try_init()
+        .map_err(|e| format!("Failed to set up logging: {}", e))?;
+    let _ = LEVEL.set(handle);
+    Ok(())
+}
+
+/// Changes the level of the subscriber installed by `init`; does nothing
+/// when the embedding application set up its own.
+pub fn set_level(level: &str) -> Result<(), String> {
+    let level = parse_level(level)?;
+    match LEVEL.get() {
+        Some(handle) => handle.reload(level).map_err(|e| e.to_string()),
+        None => Ok(()),
+    }
+}
+
+pub fn parse_level(level: &str) -> Result<LevelFilter, String> {
+    level
+        .parse()
+        .map_err(|_| format!("Unknown log level: {}", level))
+}
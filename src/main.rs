@@ -1,28 +1,169 @@
-mod config;
-mod data_generator;
-mod data_inserter;
-mod data_inserter_with_tokio;
-mod model;
-mod request;
-mod server;
-mod server_state;
+mod cli;
 
 use std::sync::Arc;
 
+use http_server::config::Config;
+use http_server::data_generator::DataGenerator;
+use http_server::data_writer::DataWriter;
+use http_server::export::{self, ExportArgs};
+use http_server::server::Server;
+use http_server::{
+    auth, config_reload, generation_spec, logging, migrate, populate, shutdown, sink,
+};
 use mysql::Pool;
-use server::Server;
-// use tokio::task::JoinHandle;
 
-use crate::config::Config;
+use crate::cli::{Command, GenerateArgs, PopulateArgs};
 
 // #[tokio::main]
 fn main() {
-    let config = Arc::new(Config::load());
-    let pool = Pool::new(config.database.url.as_str()).unwrap();
-    let server = Server::new(pool, config.clone());
-    server.run(&format!(
-        "{}:{}",
-        &config.server.host,
-        &config.server.port.to_string()
-    ));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let cli = match cli::parse(&args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
+    let config = match Config::load(cli.config.as_deref()) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = logging::init(&config.log.level) {
+        eprintln!("{}", e);
+    }
+    let result = match cli.command {
+        Command::Serve => serve(config),
+        Command::Generate(args) => generate(&config, args),
+        Command::Populate(args) => populate(config, args),
+        Command::Migrate => migrate(&config),
+        Command::Export(args) => export(&config, args),
+        Command::CheckConfig => check_config(&config),
+        Command::HashKey => hash_key(),
+    };
+    if let Err(e) = result {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+fn connect(config: &Config) -> Result<Pool, String> {
+    Pool::new(config.database.url.as_str())
+        .map_err(|e| format!("Failed to connect to the database: {}", e))
+}
+
+fn serve(config: Arc<Config>) -> Result<(), String> {
+    let pool = connect(&config)?;
+    let server = Server::new(pool, config.clone())?;
+    config_reload::watch(server.clone());
+    shutdown::watch(server.clone());
+    server.run().map_err(|e| e.to_string())
+}
+
+fn generate(config: &Config, args: GenerateArgs) -> Result<(), String> {
+    let seed = args.seed.unwrap_or_else(DataGenerator::random_seed);
+    let generator =
+        generation_spec::generator_for(config.populate.spec_file.as_deref(), &args.table, seed)?;
+    generator.check_capacity(args.count)?;
+    let sink = sink::open_file_sink(
+        &args.output,
+        args.format,
+        args.gzip,
+        generator.table(),
+        generator.columns(),
+    )
+    .map_err(|e| format!("Failed to open {}: {}", args.output, e))?;
+    let duration = DataWriter::new(sink)
+        .write(Arc::clone(&generator), args.count)
+        .map_err(|e| format!("Failed to write {}: {}", args.output, e))?;
+    println!(
+        "Wrote {} {} rows to {} in {:?} (seed {})",
+        args.count,
+        generator.table(),
+        args.output,
+        duration,
+        seed
+    );
+    Ok(())
+}
+
+fn populate(config: Arc<Config>, args: PopulateArgs) -> Result<(), String> {
+    let seed = args.seed.unwrap_or_else(DataGenerator::random_seed);
+    let generator =
+        generation_spec::generator_for(config.populate.spec_file.as_deref(), &args.table, seed)?;
+    generator.check_capacity(args.count)?;
+    let strategy = args.strategy.for_columns(generator.columns().len());
+    let pool = connect(&config)?;
+    let result = populate::populate(
+        pool,
+        config,
+        Arc::clone(&generator),
+        args.count,
+        args.mode,
+        strategy,
+    );
+    let duration = result.map_err(|e| format!("Failed to populate records: {}", e))?;
+    println!(
+        "Populated {} {} records in {:?} using {} on {} (seed {})",
+        args.count,
+        generator.table(),
+        duration,
+        strategy,
+        args.mode,
+        seed
+    );
+    Ok(())
+}
+
+fn migrate(config: &Config) -> Result<(), String> {
+    let pool = connect(config)?;
+    let applied = migrate::migrate(&pool).map_err(|e| format!("Migration failed: {}", e))?;
+    if applied.is_empty() {
+        println!("Schema is up to date");
+    }
+    for name in applied {
+        println!("Applied {}", name);
+    }
+    Ok(())
+}
+
+fn export(config: &Config, args: ExportArgs) -> Result<(), String> {
+    let pool = connect(config)?;
+    let start = std::time::Instant::now();
+    let count = export::export(&pool, &args)?;
+    println!(
+        "Exported {} {} rows to {} in {:?}",
+        count,
+        args.table,
+        args.output,
+        start.elapsed()
+    );
+    Ok(())
+}
+
+/// `Config::load` has already validated the file; this also loads the
+/// generation spec it points to.
+fn check_config(config: &Config) -> Result<(), String> {
+    if let Some(spec_file) = &config.populate.spec_file {
+        generation_spec::GenerationSpec::load(spec_file)?;
+    }
+    auth::Authenticator::new(&config.auth)?;
+    println!("{}: OK", config.path);
+    Ok(())
+}
+
+fn hash_key() -> Result<(), String> {
+    let mut key = String::new();
+    std::io::stdin()
+        .read_line(&mut key)
+        .map_err(|e| format!("Failed to read the key: {}", e))?;
+    let key = key.trim_end_matches(['\r', '\n']);
+    if key.is_empty() {
+        return Err("No key given on stdin".to_string());
+    }
+    println!("{}", auth::hash_key(key));
+    Ok(())
 }
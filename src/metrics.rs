@@ -0,0 +1,293 @@
+use mysql::{Pool, PooledConn};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upper bounds, in seconds, of the latency histogram buckets.
+const BUCKETS: [f64; 12] = [
+    0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+const METHODS: [&str; 7] = ["GET", "HEAD", "POST", "PUT", "PATCH", "DELETE", "OPTIONS"];
+
+static GLOBAL: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+/// The process-wide registry that `GET /metrics` renders.
+pub fn global() -> &'static Metrics {
+    &GLOBAL
+}
+
+#[derive(Default)]
+struct Histogram {
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(BUCKETS) {
+            if secs <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum += secs;
+    }
+
+    fn render(&self, out: &mut String, name: &str, labels: &str) {
+        let sep = if labels.is_empty() { "" } else { "," };
+        for (count, bound) in self.buckets.iter().zip(BUCKETS) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}{}le=\"{}\"}} {}",
+                name, labels, sep, bound, count
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{}{}le=\"+Inf\"}} {}",
+            name, labels, sep, self.count
+        );
+        let braced = if labels.is_empty() {
+            String::new()
+        } else {
+            format!("{{{}}}", labels)
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, braced, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, braced, self.count);
+    }
+}
+
+/// Rows inserted by one running populate job, for the current-rate gauge.
+struct JobProgress {
+    started: Instant,
+    inserted: AtomicU64,
+}
+
+/// Counters for HTTP traffic, database checkouts and populate jobs.
+#[derive(Default)]
+pub struct Metrics {
+    /// Keyed by route, method and status.
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// Keyed by route and method.
+    latency: Mutex<BTreeMap<(String, String), Histogram>>,
+    active_connections: AtomicI64,
+    pool_checkout: Mutex<Histogram>,
+    pool_checkout_errors: AtomicU64,
+    rows_generated: AtomicU64,
+    rows_inserted: AtomicU64,
+    rows_failed: AtomicU64,
+    jobs: Mutex<Vec<Arc<JobProgress>>>,
+}
+
+/// Counts a connection as active until dropped.
+pub struct ConnectionGuard<'a>(&'a Metrics);
+
+impl Drop for ConnectionGuard<'_> {
+    fn drop(&mut self) {
+        self.0.active_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Reports one populate job's progress; the job stops counting towards the
+/// current rate when dropped.
+pub struct PopulateJob {
+    metrics: &'static Metrics,
+    progress: Arc<JobProgress>,
+}
+
+impl PopulateJob {
+    pub fn generated(&self, rows: usize) {
+        self.metrics
+            .rows_generated
+            .fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    pub fn inserted(&self, rows: usize) {
+        self.metrics
+            .rows_inserted
+            .fetch_add(rows as u64, Ordering::Relaxed);
+        self.progress
+            .inserted
+            .fetch_add(rows as u64, Ordering::Relaxed);
+    }
+
+    pub fn failed(&self, rows: usize) {
+        self.metrics
+            .rows_failed
+            .fetch_add(rows as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for PopulateJob {
+    fn drop(&mut self) {
+        let mut jobs = self.metrics.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.retain(|job| !Arc::ptr_eq(job, &self.progress));
+    }
+}
+
+fn method_label(method: &str) -> &str {
+    METHODS
+        .iter()
+        .find(|known| known.eq_ignore_ascii_case(method))
+        .copied()
+        .unwrap_or("OTHER")
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Metrics {
+    /// Records a finished request. `route` is the matched route pattern so
+    /// that arbitrary paths do not each get their own series.
+    pub fn record_request(&self, route: &str, method: &str, status: u16, latency: Duration) {
+        let method = method_label(method).to_string();
+        let mut requests = self.requests.lock().unwrap_or_else(|e| e.into_inner());
+        *requests
+            .entry((route.to_string(), method.clone(), status))
+            .or_default() += 1;
+        drop(requests);
+        self.latency
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry((route.to_string(), method))
+            .or_default()
+            .observe(latency);
+    }
+
+    pub fn connection(&self) -> ConnectionGuard<'_> {
+        self.active_connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self)
+    }
+
+    /// `pool.get_conn()`, timing how long the checkout waited.
+    pub fn get_conn(&self, pool: &Pool) -> mysql::Result<PooledConn> {
+        let start = Instant::now();
+        let conn = pool.get_conn();
+        self.pool_checkout
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .observe(start.elapsed());
+        if conn.is_err() {
+            self.pool_checkout_errors.fetch_add(1, Ordering::Relaxed);
+        }
+        conn
+    }
+
+    pub fn populate_job(&'static self) -> PopulateJob {
+        let progress = Arc::new(JobProgress {
+            started: Instant::now(),
+            inserted: AtomicU64::new(0),
+        });
+        self.jobs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(Arc::clone(&progress));
+        PopulateJob {
+            metrics: self,
+            progress,
+        }
+    }
+
+    /// Prometheus text exposition format.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP http_requests_total HTTP requests by route, method and status.\n");
+        out.push_str("# TYPE http_requests_total counter\n");
+        for ((route, method, status), count) in self
+            .requests
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            let _ = writeln!(
+                out,
+                "http_requests_total{{route=\"{}\",method=\"{}\",status=\"{}\"}} {}",
+                escape(route),
+                method,
+                status,
+                count
+            );
+        }
+
+        out.push_str("# HELP http_request_duration_seconds Time to handle a request.\n");
+        out.push_str("# TYPE http_request_duration_seconds histogram\n");
+        for ((route, method), histogram) in self
+            .latency
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+        {
+            let labels = format!("route=\"{}\",method=\"{}\"", escape(route), method);
+            histogram.render(&mut out, "http_request_duration_seconds", &labels);
+        }
+
+        out.push_str("# HELP http_active_connections Connections being handled.\n");
+        out.push_str("# TYPE http_active_connections gauge\n");
+        let _ = writeln!(
+            out,
+            "http_active_connections {}",
+            self.active_connections.load(Ordering::Relaxed)
+        );
+
+        out.push_str("# HELP mysql_pool_checkout_seconds Wait for a pooled connection.\n");
+        out.push_str("# TYPE mysql_pool_checkout_seconds histogram\n");
+        self.pool_checkout
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .render(&mut out, "mysql_pool_checkout_seconds", "");
+        out.push_str("# HELP mysql_pool_checkout_errors_total Failed checkouts.\n");
+        out.push_str("# TYPE mysql_pool_checkout_errors_total counter\n");
+        let _ = writeln!(
+            out,
+            "mysql_pool_checkout_errors_total {}",
+            self.pool_checkout_errors.load(Ordering::Relaxed)
+        );
+
+        for (name, help, counter) in [
+            (
+                "populate_rows_generated_total",
+                "Rows produced by generators.",
+                &self.rows_generated,
+            ),
+            (
+                "populate_rows_inserted_total",
+                "Rows written to the database.",
+                &self.rows_inserted,
+            ),
+            (
+                "populate_rows_failed_total",
+                "Rows in batches whose insert failed.",
+                &self.rows_failed,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, counter.load(Ordering::Relaxed));
+        }
+
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        let rate = jobs.iter().fold(0.0, |rate, job| {
+            rate + job.inserted.load(Ordering::Relaxed) as f64
+                / job.started.elapsed().as_secs_f64().max(0.001)
+        });
+        out.push_str("# HELP populate_jobs_running Populate jobs in progress.\n");
+        out.push_str("# TYPE populate_jobs_running gauge\n");
+        let _ = writeln!(out, "populate_jobs_running {}", jobs.len());
+        out.push_str(
+            "# HELP populate_rows_per_second Insert rate of running jobs since they started.\n",
+        );
+        out.push_str("# TYPE populate_rows_per_second gauge\n");
+        let _ = writeln!(out, "populate_rows_per_second {}", rate);
+        out
+    }
+}
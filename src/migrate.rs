@@ -0,0 +1,71 @@
+use crate::metrics;
+use mysql::prelude::*;
+use mysql::{Pool, PooledConn, params};
+
+/// Schema changes embedded in the binary, applied in order by `migrate`.
+const MIGRATIONS: &[(u32, &str, &str)] = &[
+    (
+        1,
+        "create_person",
+        include_str!("../migrations/0001_create_person.sql"),
+    ),
+    (
+        2,
+        "create_orders",
+        include_str!("../migrations/0002_create_orders.sql"),
+    ),
+    (
+        3,
+        "person_updated_by",
+        include_str!("../migrations/0003_person_updated_by.sql"),
+    ),
+];
+
+const CREATE_SCHEMA_MIGRATIONS: &str = "CREATE TABLE IF NOT EXISTS `schema_migrations` (
+    `version` INT UNSIGNED NOT NULL PRIMARY KEY,
+    `name` VARCHAR(255) NOT NULL,
+    `applied_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+)";
+
+/// Applies the migrations not yet recorded in `schema_migrations` and returns
+/// the names of those it ran.
+pub fn migrate(pool: &Pool) -> mysql::Result<Vec<String>> {
+    let mut conn = metrics::global().get_conn(pool)?;
+    conn.query_drop(CREATE_SCHEMA_MIGRATIONS)?;
+    let applied: Vec<u32> = conn.query("SELECT version FROM schema_migrations")?;
+
+    let mut ran = Vec::new();
+    for &(version, name, sql) in MIGRATIONS {
+        if applied.contains(&version) {
+            continue;
+        }
+        // MySQL commits DDL implicitly, so each migration is recorded right
+        // after it succeeds rather than in a shared transaction.
+        conn.query_drop(sql)?;
+        conn.exec_drop(
+            "INSERT INTO schema_migrations (version, name) VALUES (:version, :name)",
+            params! { "version" => version, "name" => name },
+        )?;
+        ran.push(migration_name(version, name));
+    }
+    Ok(ran)
+}
+
+fn migration_name(version: u32, name: &str) -> String {
+    format!("{:04}_{}", version, name)
+}
+
+/// Names of the migrations `migrate` would run, without changing anything.
+pub fn pending(conn: &mut PooledConn) -> mysql::Result<Vec<String>> {
+    let applied: Vec<u32> = match conn.query("SELECT version FROM schema_migrations") {
+        Ok(applied) => applied,
+        // ER_NO_SUCH_TABLE: nothing has been migrated yet.
+        Err(mysql::Error::MySqlError(e)) if e.code == 1146 => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    Ok(MIGRATIONS
+        .iter()
+        .filter(|(version, _, _)| !applied.contains(version))
+        .map(|&(version, name, _)| migration_name(version, name))
+        .collect())
+}
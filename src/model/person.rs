@@ -9,3 +9,15 @@ pub struct Person {
     pub state: String,
     pub version: u32,
 }
+
+/// Fields of a `Person` that clients create or replace; the store assigns
+/// `id` and `version`.
+#[derive(Clone)]
+pub struct NewPerson {
+    pub name: String,
+    pub email: String,
+    pub phone: String,
+    pub address: String,
+    pub city: String,
+    pub state: String,
+}
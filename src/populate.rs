@@ -0,0 +1,76 @@
+use crate::config::Config;
+use crate::data_inserter::DataInserter;
+use crate::data_inserter_with_tokio::DataInserterWithTokio;
+use crate::insert_strategy::InsertStrategy;
+use crate::row_generator::RowGenerator;
+use mysql::Pool;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tracing::{error, info, info_span};
+
+/// Which inserter a populate job runs on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PopulateMode {
+    /// `DataInserter`: plain threads, no rate limit.
+    Threads,
+    /// `DataInserterWithTokio`: limited by `[populate.rate_limit]`.
+    Tokio,
+}
+
+impl PopulateMode {
+    pub fn parse(mode: &str) -> Result<Self, String> {
+        match mode {
+            "threads" => Ok(PopulateMode::Threads),
+            "tokio" => Ok(PopulateMode::Tokio),
+            other => Err(format!("Unknown populate mode: {}", other)),
+        }
+    }
+}
+
+impl fmt::Display for PopulateMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PopulateMode::Threads => write!(f, "threads"),
+            PopulateMode::Tokio => write!(f, "tokio"),
+        }
+    }
+}
+
+/// Inserts `count` rows from `generator` and returns how long it took, or
+/// the first error of an inserter. Once one inserter fails the others stop
+/// and roll back too, so nothing is left behind; only a commit that fails
+/// after another inserter's succeeded leaves that one's rows in place.
+/// Blocks the calling thread, so call it from outside async code.
+pub fn populate(
+    pool: Pool,
+    config: Arc<Config>,
+    generator: Arc<dyn RowGenerator>,
+    count: u32,
+    mode: PopulateMode,
+    strategy: InsertStrategy,
+) -> Result<Duration, String> {
+    let strategy = strategy.for_columns(generator.columns().len());
+    let span = info_span!(
+        "populate",
+        table = generator.table(),
+        count,
+        %mode,
+        %strategy
+    );
+    let _entered = span.enter();
+    let result = match mode {
+        PopulateMode::Threads => DataInserter::new(pool).populate(generator, count, strategy),
+        PopulateMode::Tokio => Runtime::new()
+            .map_err(|e| format!("Failed to start runtime: {}", e))?
+            .block_on(
+                DataInserterWithTokio::new(pool, config).populate(generator, count, strategy),
+            ),
+    };
+    match &result {
+        Ok(duration) => info!("Finished in {:?}", duration),
+        Err(e) => error!("Failed: {}", e),
+    }
+    result
+}
@@ -1,9 +1,109 @@
+use crate::auth::Principal;
+use crate::config::ServerConfig;
+use crate::response::Response;
+use flate2::read::{DeflateDecoder, MultiGzDecoder, ZlibDecoder};
 use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::io::{self, Read};
+use std::net::IpAddr;
+use std::sync::LazyLock;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Random for each process, so that ids from different runs do not collide.
+static REQUEST_ID_PREFIX: LazyLock<u64> = LazyLock::new(|| RandomState::new().hash_one(0));
+static REQUEST_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Largest request line plus headers accepted.
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+
+/// Why `Request::read` did not produce a request.
+pub enum ReadError {
+    Io(io::Error),
+    /// The request was malformed or too large; send this instead of handling
+    /// it.
+    Rejected(Response),
+}
+
+fn reject(status: u16, message: &str) -> ReadError {
+    ReadError::Rejected(Response::text(status, message.to_string()))
+}
+
+/// Offset just past the blank line that ends the headers.
+fn head_end(buffer: &[u8]) -> Option<usize> {
+    let crlf = buffer
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .map(|i| i + 4);
+    let lf = buffer.windows(2).position(|w| w == b"\n\n").map(|i| i + 2);
+    match (crlf, lf) {
+        (Some(crlf), Some(lf)) => Some(crlf.min(lf)),
+        (end, None) | (None, end) => end,
+    }
+}
+
+/// Whether `body` starts with a zlib header. Some clients send raw deflate
+/// data for `Content-Encoding: deflate` instead.
+fn is_zlib(body: &[u8]) -> bool {
+    matches!(body, [cmf, flg, ..] if cmf & 0x0f == 8 && (u16::from(*cmf) << 8 | u16::from(*flg)) % 31 == 0)
+}
+
+/// Undoes each `Content-Encoding` in turn, last applied first, refusing to
+/// produce more than `max` bytes.
+fn decode_body(encodings: &str, mut body: Vec<u8>, max: usize) -> Result<Vec<u8>, ReadError> {
+    for encoding in encodings.split(',').rev().map(str::trim) {
+        let decoder: Box<dyn Read + '_> = match encoding.to_ascii_lowercase().as_str() {
+            "" | "identity" => continue,
+            "gzip" | "x-gzip" => Box::new(MultiGzDecoder::new(body.as_slice())),
+            "deflate" if is_zlib(&body) => Box::new(ZlibDecoder::new(body.as_slice())),
+            "deflate" => Box::new(DeflateDecoder::new(body.as_slice())),
+            _ => {
+                return Err(ReadError::Rejected(
+                    Response::text(415, format!("Unsupported Content-Encoding: {}", encoding))
+                        .with_header("Accept-Encoding", "gzip, deflate"),
+                ));
+            }
+        };
+        let mut decoded = Vec::new();
+        decoder
+            .take(max as u64 + 1)
+            .read_to_end(&mut decoded)
+            .map_err(|_| reject(400, "Malformed compressed body"))?;
+        if decoded.len() > max {
+            return Err(reject(413, "Decompressed body too large"));
+        }
+        body = decoded;
+    }
+    Ok(body)
+}
+
+/// The client's `X-Request-Id` when it is short printable ASCII, else a new
+/// one from the process's random prefix and a counter.
+fn request_id(header: Option<&String>) -> String {
+    match header {
+        Some(id)
+            if !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic()) =>
+        {
+            id.clone()
+        }
+        _ => {
+            let count = REQUEST_COUNT.fetch_add(1, Ordering::Relaxed);
+            format!("{:016x}-{:x}", *REQUEST_ID_PREFIX, count)
+        }
+    }
+}
 
 pub struct Request {
     pub method: String,
     pub path: String,
+    pub version: String,
+    /// Correlates the request across logs; see `request_id`.
+    pub id: String,
+    /// Header names are stored lowercased.
+    pub headers: HashMap<String, String>,
     pub body: String,
+    pub peer_addr: Option<IpAddr>,
+    /// Who sent the request, once `[auth]` has checked its credentials.
+    pub principal: Option<Principal>,
 }
 
 impl Request {
@@ -21,28 +121,150 @@ impl Request {
 
         let method = parts[0].to_string();
         let path = parts[1].to_string();
+        let version = parts.get(2).unwrap_or(&"HTTP/1.0").to_string();
+        let headers: HashMap<String, String> = lines
+            .iter()
+            .skip(1)
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .collect();
         let body = lines
             .iter()
             .skip_while(|line| !line.is_empty())
             .skip(1)
-            .map(|line| *line) // Dereference &&str to &str
+            .copied()
             .collect::<Vec<&str>>()
             .join("\n");
 
-        Some(Request { method, path, body })
+        Some(Request {
+            method,
+            path,
+            version,
+            id: request_id(headers.get("x-request-id")),
+            headers,
+            body,
+            peer_addr: None,
+            principal: None,
+        })
+    }
+
+    /// Reads one request from `stream`: the head, then `Content-Length`
+    /// bytes of body, decompressed according to `Content-Encoding`, within
+    /// `config.body_limits`.
+    pub fn read(stream: &mut impl Read, config: &ServerConfig) -> Result<Self, ReadError> {
+        let mut buffer = Vec::new();
+        let mut chunk = [0; 4096];
+        let end = loop {
+            if let Some(end) = head_end(&buffer) {
+                break end;
+            }
+            if buffer.len() > MAX_HEAD_BYTES {
+                return Err(reject(431, "Request Header Fields Too Large"));
+            }
+            let n = stream.read(&mut chunk).map_err(ReadError::Io)?;
+            if n == 0 {
+                // The client sent a request without a body and closed.
+                break buffer.len();
+            }
+            buffer.extend_from_slice(&chunk[..n]);
+        };
+        let mut request = Request::parse(&String::from_utf8_lossy(&buffer[..end]))
+            .ok_or_else(|| reject(400, "Bad Request"))?;
+
+        if request
+            .header("transfer-encoding")
+            .is_some_and(|encoding| !encoding.eq_ignore_ascii_case("identity"))
+        {
+            return Err(reject(411, "Length Required"));
+        }
+        let length = match request.header("content-length") {
+            Some(length) => length
+                .parse::<usize>()
+                .map_err(|_| reject(400, "Invalid Content-Length"))?,
+            None => 0,
+        };
+        let (max_body, max_decoded) =
+            config.body_limits(&request.method, request.path_without_query());
+        if length > max_body {
+            return Err(reject(413, "Payload Too Large"));
+        }
+        let mut body = buffer.split_off(end);
+        while body.len() < length {
+            let n = stream.read(&mut chunk).map_err(ReadError::Io)?;
+            if n == 0 {
+                return Err(reject(400, "Incomplete body"));
+            }
+            body.extend_from_slice(&chunk[..n]);
+        }
+        body.truncate(length);
+
+        // Handlers only ever see the decoded body.
+        if let Some(encodings) = request.headers.remove("content-encoding") {
+            body = decode_body(&encodings, body, max_decoded)?;
+        }
+        request.body = String::from_utf8_lossy(&body).into_owned();
+        Ok(request)
+    }
+
+    pub fn path_without_query(&self) -> &str {
+        self.path.split('?').next().unwrap_or("")
+    }
+
+    pub fn query_params(&self) -> HashMap<String, String> {
+        parse_pairs(self.path.split_once('?').map_or("", |(_, query)| query))
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// The token of an `Authorization: Bearer` header.
+    pub fn bearer_token(&self) -> Option<&str> {
+        let (scheme, token) = self.header("authorization")?.split_once(' ')?;
+        scheme.eq_ignore_ascii_case("bearer").then(|| token.trim())
     }
 
     pub fn parse_body(&self) -> HashMap<String, String> {
-        self.body
-            .split('&')
-            .filter_map(|pair| {
-                let kv: Vec<&str> = pair.splitn(2, '=').collect();
-                if kv.len() == 2 {
-                    Some((kv[0].to_string(), kv[1].to_string()))
-                } else {
-                    None
+        parse_pairs(&self.body)
+    }
+}
+
+/// Undoes `application/x-www-form-urlencoded` escaping: `+` is a space and
+/// `%XX` a byte. Malformed escapes are kept as they are.
+fn url_decode(input: &str) -> String {
+    let bytes = input.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => match input
+                .get(i + 1..i + 3)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                Some(byte) => {
+                    decoded.push(byte);
+                    i += 2;
                 }
-            })
-            .collect()
+                None => decoded.push(b'%'),
+            },
+            byte => decoded.push(byte),
+        }
+        i += 1;
     }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn parse_pairs(input: &str) -> HashMap<String, String> {
+    input
+        .split('&')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=')?;
+            Some((url_decode(key), url_decode(value)))
+        })
+        .collect()
 }
@@ -0,0 +1,131 @@
+use std::io::{self, BufWriter, Write};
+
+/// Produces a body too large to hold in memory, writing it as it goes.
+pub type BodyStream = Box<dyn FnOnce(&mut dyn Write) -> io::Result<()> + Send>;
+
+/// Buffer between a `BodyStream` and the connection; each flush of it
+/// becomes one chunk.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    /// Sent with `Transfer-Encoding: chunked` instead of `body` when set.
+    pub stream: Option<BodyStream>,
+}
+
+/// Frames everything written to it as HTTP/1.1 chunks and counts the bytes
+/// of payload.
+struct Chunked<'a, W: Write> {
+    inner: &'a mut W,
+    written: u64,
+}
+
+impl<W: Write> Write for Chunked<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.inner, "{:x}\r\n", buf.len())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        self.written += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Response {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Response {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.into(),
+            stream: None,
+        }
+    }
+
+    /// A response whose body `stream` writes while it is being sent.
+    pub fn stream(
+        status: u16,
+        content_type: &str,
+        stream: impl FnOnce(&mut dyn Write) -> io::Result<()> + Send + 'static,
+    ) -> Self {
+        Response {
+            stream: Some(Box::new(stream)),
+            ..Response::new(status, content_type, Vec::new())
+        }
+    }
+
+    pub fn text(status: u16, body: impl Into<Vec<u8>>) -> Self {
+        Response::new(status, "text/plain", body)
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl ToString) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Sends the response and returns how many body bytes were written. A
+    /// streamed body is consumed; if it fails part way the final chunk is
+    /// left out so that clients see the body as truncated.
+    pub fn write_to(&mut self, stream: &mut impl Write) -> io::Result<u64> {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\n",
+            self.status,
+            reason_phrase(self.status)
+        );
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        let Some(body) = self.stream.take() else {
+            head.push_str(&format!("Content-Length: {}\r\n\r\n", self.body.len()));
+            stream.write_all(head.as_bytes())?;
+            stream.write_all(&self.body)?;
+            stream.flush()?;
+            return Ok(self.body.len() as u64);
+        };
+        head.push_str("Transfer-Encoding: chunked\r\n\r\n");
+        stream.write_all(head.as_bytes())?;
+        let mut chunked = Chunked {
+            inner: stream,
+            written: 0,
+        };
+        let mut buffered = BufWriter::with_capacity(CHUNK_SIZE, &mut chunked);
+        body(&mut buffered)?;
+        buffered.flush()?;
+        drop(buffered);
+        let written = chunked.written;
+        stream.write_all(b"0\r\n\r\n")?;
+        stream.flush()?;
+        Ok(written)
+    }
+}
+
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        308 => "Permanent Redirect",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        415 => "Unsupported Media Type",
+        422 => "Unprocessable Entity",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "",
+    }
+}
@@ -0,0 +1,96 @@
+use crate::request::Request;
+use crate::response::Response;
+use std::sync::Arc;
+
+/// Function that answers a request matched by a `Router`.
+pub type Handler = Arc<dyn Fn(&Request) -> Response + Send + Sync>;
+
+struct Route {
+    method: String,
+    pattern: String,
+    handler: Handler,
+}
+
+impl Route {
+    fn matches_path(&self, path: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => path.starts_with(prefix),
+            None => path == self.pattern,
+        }
+    }
+}
+
+/// Dispatches requests to handlers by method and path. Routes are tried in
+/// the order they were added.
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: Vec<Arc<Route>>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    /// Adds a route. `pattern` matches the path (without the query string)
+    /// exactly, or as a prefix when it ends in `*`, as in `/person/*`.
+    pub fn route(
+        mut self,
+        method: &str,
+        pattern: &str,
+        handler: impl Fn(&Request) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        self.routes.push(Arc::new(Route {
+            method: method.to_ascii_uppercase(),
+            pattern: pattern.to_string(),
+            handler: Arc::new(handler),
+        }));
+        self
+    }
+
+    /// Appends `other`'s routes after this router's.
+    pub fn merge(mut self, other: Router) -> Self {
+        self.routes.extend(other.routes);
+        self
+    }
+
+    /// Pattern of the first route whose path matches, used to label metrics.
+    pub fn pattern_for(&self, request: &Request) -> Option<&str> {
+        let path = request.path_without_query();
+        self.routes
+            .iter()
+            .find(|route| route.matches_path(path))
+            .map(|route| route.pattern.as_str())
+    }
+
+    /// Methods registered for `path`, in route order.
+    pub fn methods_for(&self, path: &str) -> Vec<&str> {
+        let mut methods: Vec<&str> = Vec::new();
+        for route in self.routes.iter().filter(|route| route.matches_path(path)) {
+            if !methods.contains(&route.method.as_str()) {
+                methods.push(&route.method);
+            }
+        }
+        methods
+    }
+
+    /// Runs the first matching handler. Otherwise answers `OPTIONS` with the
+    /// path's methods, and anything else with 405 when only the method
+    /// differs, else 404.
+    pub fn handle(&self, request: &Request) -> Response {
+        let path = request.path_without_query();
+        if let Some(route) = self.routes.iter().find(|route| {
+            route.matches_path(path) && route.method.eq_ignore_ascii_case(&request.method)
+        }) {
+            return (route.handler)(request);
+        }
+        let allowed = self.methods_for(path);
+        if allowed.is_empty() {
+            Response::text(404, "404 - Endpoint not found")
+        } else if request.method.eq_ignore_ascii_case("OPTIONS") {
+            Response::text(204, "").with_header("Allow", format!("{}, OPTIONS", allowed.join(", ")))
+        } else {
+            Response::text(405, "Method Not Allowed").with_header("Allow", allowed.join(", "))
+        }
+    }
+}
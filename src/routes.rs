@@ -0,0 +1,580 @@
+use crate::config::{Config, ValidationConfig};
+use crate::data_generator::DataGenerator;
+use crate::export;
+use crate::generation_spec;
+use crate::import;
+use crate::insert_strategy::InsertStrategy;
+use crate::metrics;
+use crate::migrate;
+use crate::model::person::{NewPerson, Person};
+use crate::populate::{self, PopulateMode};
+use crate::request::Request;
+use crate::response::Response;
+use crate::router::Router;
+use crate::row_generator::RowGenerator;
+use crate::server::Lifecycle;
+use crate::sink::OutputFormat;
+use crate::store::{PersonStore, Upsert, UpsertOutcome};
+use crate::validation;
+use arc_swap::ArcSwap;
+use mysql::Pool;
+use mysql::prelude::Queryable;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+/// `GET /metrics` in the Prometheus text format.
+pub fn metrics_routes() -> Router {
+    Router::new().route("GET", "/metrics", |_| {
+        Response::new(200, "text/plain; version=0.0.4", metrics::global().render())
+    })
+}
+
+/// How long `/readyz` waits for the database before reporting it down.
+const READY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// `GET /healthz` for liveness and `GET /readyz` for readiness.
+pub fn health_routes(pool: Option<Pool>, lifecycle: Arc<Lifecycle>) -> Router {
+    Router::new()
+        .route("GET", "/healthz", |_| {
+            Response::new(200, "application/json", json!({"status": "ok"}).to_string())
+        })
+        .route("GET", "/readyz", move |_| {
+            readiness(pool.as_ref(), &lifecycle)
+        })
+}
+
+/// Runs the database checks on another thread so a hung connect cannot hold
+/// the probe past `READY_TIMEOUT`.
+fn database_checks(pool: &Pool) -> (Value, Value) {
+    let (tx, rx) = mpsc::channel();
+    let pool = pool.clone();
+    thread::spawn(move || {
+        let start = Instant::now();
+        let result = metrics::global().get_conn(&pool).and_then(|mut conn| {
+            conn.query_drop("SELECT 1")?;
+            let latency = start.elapsed();
+            Ok((latency, migrate::pending(&mut conn)?))
+        });
+        let _ = tx.send(result);
+    });
+    match rx.recv_timeout(READY_TIMEOUT) {
+        Ok(Ok((latency, pending))) => (
+            json!({
+                "status": "ok",
+                "latency_ms": latency.as_secs_f64() * 1000.0,
+            }),
+            if pending.is_empty() {
+                json!({"status": "ok"})
+            } else {
+                json!({"status": "pending", "pending": pending})
+            },
+        ),
+        Ok(Err(e)) => (
+            json!({"status": "error", "error": e.to_string()}),
+            json!({"status": "unknown"}),
+        ),
+        Err(_) => (
+            json!({"status": "error", "error": format!("timed out after {:?}", READY_TIMEOUT)}),
+            json!({"status": "unknown"}),
+        ),
+    }
+}
+
+fn readiness(pool: Option<&Pool>, lifecycle: &Lifecycle) -> Response {
+    let mut checks = serde_json::Map::new();
+    if let Some(pool) = pool {
+        let (database, migrations) = database_checks(pool);
+        checks.insert("database".to_string(), database);
+        checks.insert("migrations".to_string(), migrations);
+    }
+    checks.insert(
+        "shutdown".to_string(),
+        json!({"status": if lifecycle.is_draining() { "draining" } else { "ok" }}),
+    );
+
+    let ready = checks.values().all(|check| check["status"] == "ok");
+    let body = json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "checks": checks,
+    });
+    Response::new(
+        if ready { 200 } else { 503 },
+        "application/json",
+        body.to_string(),
+    )
+}
+
+/// Most ids or persons one batch request may carry.
+const MAX_BATCH_ITEMS: usize = 1000;
+
+/// `GET /person/{id}`, `POST /person` and `PUT /person/{id}`, plus
+/// `POST /persons:batchGet` and `POST /persons:batchUpsert` for many at once.
+/// `POST` and `PUT` take a form of `name`, `email`, `phone`, `address`, `city`
+/// and `state`, the columns of the `person` table; the `age` field they once
+/// took is no longer read. Writes are checked against `[validation]`.
+pub fn person_routes(store: Arc<dyn PersonStore>, config: Arc<ArcSwap<Config>>) -> Router {
+    let get = Arc::clone(&store);
+    let create = Arc::clone(&store);
+    let batch_get = Arc::clone(&store);
+    let batch_upsert = Arc::clone(&store);
+    let create_config = Arc::clone(&config);
+    let batch_config = Arc::clone(&config);
+    Router::new()
+        .route("POST", "/persons:batchGet", move |request| {
+            batch_get_persons(batch_get.as_ref(), request)
+        })
+        .route("POST", "/persons:batchUpsert", move |request| {
+            batch_upsert_persons(
+                batch_upsert.as_ref(),
+                &batch_config.load().validation,
+                request,
+            )
+        })
+        .route("GET", "/person/*", move |request| {
+            get_person(get.as_ref(), request)
+        })
+        .route("POST", "/person", move |request| {
+            create_person(create.as_ref(), &create_config.load().validation, request)
+        })
+        .route("PUT", "/person/*", move |request| {
+            update_person(store.as_ref(), &config.load().validation, request)
+        })
+}
+
+/// `POST /populate` on the rate-limited tokio inserter and `POST /populate2`
+/// on plain threads.
+pub fn populate_routes(pool: Pool, config: Arc<ArcSwap<Config>>) -> Router {
+    let threads_pool = pool.clone();
+    let threads_config = Arc::clone(&config);
+    Router::new()
+        .route("POST", "/populate2", move |request| {
+            populate(
+                &threads_pool,
+                threads_config.load_full(),
+                request,
+                PopulateMode::Threads,
+            )
+        })
+        .route("POST", "/populate", move |request| {
+            populate(&pool, config.load_full(), request, PopulateMode::Tokio)
+        })
+}
+
+/// `GET /persons/export?format=csv|ndjson`, streamed a page of MySQL rows at
+/// a time, and `POST /persons/import` to load the same formats from uploads
+/// of up to `server.max_import_bytes`.
+pub fn export_routes(pool: Pool, config: Arc<ArcSwap<Config>>) -> Router {
+    let import_pool = pool.clone();
+    Router::new()
+        .route("GET", "/persons/export", move |request| {
+            export_persons(&pool, request)
+        })
+        .route("POST", "/persons/import", move |request| {
+            import_persons(&import_pool, &config.load().validation, request)
+        })
+}
+
+fn person_id(request: &Request) -> Option<u32> {
+    request
+        .path_without_query()
+        .strip_prefix("/person/")?
+        .parse()
+        .ok()
+}
+
+fn new_person(request: &Request, config: &ValidationConfig) -> Result<NewPerson, Response> {
+    let mut params = request.parse_body();
+    let mut field = |name: &str| params.remove(name).unwrap_or_default();
+    let person = NewPerson {
+        name: field("name"),
+        email: field("email"),
+        phone: field("phone"),
+        address: field("address"),
+        city: field("city"),
+        state: field("state"),
+    };
+    let errors = validation::validate(config, &person);
+    if !errors.is_empty() {
+        return Err(validation::unprocessable(&errors));
+    }
+    Ok(person)
+}
+
+fn person_json(person: &Person) -> Value {
+    json!({
+        "id": person.id,
+        "name": person.name,
+        "email": person.email,
+        "phone": person.phone,
+        "address": person.address,
+        "city": person.city,
+        "state": person.state,
+        "version": person.version,
+    })
+}
+
+/// The array under `key` in a JSON body, at most `MAX_BATCH_ITEMS` long.
+fn batch_items(request: &Request, key: &str) -> Result<Vec<Value>, Response> {
+    let mut body: Value = serde_json::from_str(&request.body)
+        .map_err(|e| Response::text(400, format!("Invalid JSON: {}", e)))?;
+    let Some(Value::Array(items)) = body.get_mut(key).map(Value::take) else {
+        return Err(Response::text(400, format!("Missing {} array", key)));
+    };
+    if items.len() > MAX_BATCH_ITEMS {
+        return Err(Response::text(
+            400,
+            format!("At most {} {} per request", MAX_BATCH_ITEMS, key),
+        ));
+    }
+    Ok(items)
+}
+
+fn json_id(value: &Value) -> Option<u32> {
+    value.as_u64().and_then(|id| u32::try_from(id).ok())
+}
+
+/// Answers with one result per requested id, in request order.
+fn batch_get_persons(store: &dyn PersonStore, request: &Request) -> Response {
+    let items = match batch_items(request, "ids") {
+        Ok(items) => items,
+        Err(response) => return response,
+    };
+    let Some(ids) = items.iter().map(json_id).collect::<Option<Vec<u32>>>() else {
+        return Response::text(400, "Invalid id in ids");
+    };
+    let mut unique = ids.clone();
+    unique.sort_unstable();
+    unique.dedup();
+    let persons: HashMap<u32, Person> = match store.get_many(&unique) {
+        Ok(persons) => persons
+            .into_iter()
+            .map(|person| (person.id, person))
+            .collect(),
+        Err(e) => return store_error(e),
+    };
+    let results: Vec<Value> = ids
+        .iter()
+        .map(|id| match persons.get(id) {
+            Some(person) => json!({"id": id, "person": person_json(person)}),
+            None => json!({"id": id, "error": "Person not found"}),
+        })
+        .collect();
+    Response::new(
+        200,
+        "application/json",
+        json!({"results": results}).to_string(),
+    )
+}
+
+fn upsert_item(item: &Value) -> Result<Upsert, String> {
+    let Some(object) = item.as_object() else {
+        return Err("Expected a JSON object".to_string());
+    };
+    let id = match object.get("id") {
+        None | Some(Value::Null) => None,
+        Some(id) => Some(json_id(id).ok_or("Invalid id")?),
+    };
+    let field = |name: &str| match object.get(name) {
+        None | Some(Value::Null) => Ok(String::new()),
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(_) => Err(format!("{} must be a string", name)),
+    };
+    let person = NewPerson {
+        name: field("name")?,
+        email: field("email")?,
+        phone: field("phone")?,
+        address: field("address")?,
+        city: field("city")?,
+        state: field("state")?,
+    };
+    Ok(Upsert { id, person })
+}
+
+/// Applies the valid items together and answers with one result per item,
+/// in request order. Invalid items are reported and skipped.
+fn batch_upsert_persons(
+    store: &dyn PersonStore,
+    config: &ValidationConfig,
+    request: &Request,
+) -> Response {
+    let items = match batch_items(request, "persons") {
+        Ok(items) => items,
+        Err(response) => return response,
+    };
+    let mut results: Vec<Value> = Vec::with_capacity(items.len());
+    let mut upserts = Vec::new();
+    // Index into `results` of each upsert.
+    let mut positions = Vec::new();
+    // Item each id, or email without an id, first appeared in.
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for (index, item) in items.iter().enumerate() {
+        let upsert = match upsert_item(item) {
+            Ok(upsert) => upsert,
+            Err(e) => {
+                results.push(json!({"index": index, "status": "invalid", "error": e}));
+                continue;
+            }
+        };
+        let errors = validation::validate(config, &upsert.person);
+        if !errors.is_empty() {
+            results.push(json!({
+                "index": index,
+                "status": "invalid",
+                "errors": validation::errors_json(&errors),
+            }));
+            continue;
+        }
+        let key = match upsert.id {
+            Some(id) => format!("id {}", id),
+            None => format!("email {}", upsert.person.email.to_lowercase()),
+        };
+        if let Some(first) = seen.get(&key) {
+            results.push(json!({
+                "index": index,
+                "status": "invalid",
+                "error": format!("Same {} as item {}", key, first),
+            }));
+            continue;
+        }
+        seen.insert(key, index);
+        positions.push(index);
+        upserts.push(upsert);
+        results.push(Value::Null);
+    }
+
+    let outcomes = match store.upsert_many(&upserts, actor(request)) {
+        Ok(outcomes) => outcomes,
+        Err(e) => return store_error(e),
+    };
+    for (index, outcome) in positions.into_iter().zip(outcomes) {
+        results[index] = match outcome {
+            UpsertOutcome::Created(id) => json!({"index": index, "status": "created", "id": id}),
+            UpsertOutcome::Updated(id) => json!({"index": index, "status": "updated", "id": id}),
+            UpsertOutcome::NotFound => json!({
+                "index": index,
+                "status": "not_found",
+                "error": "Person not found",
+            }),
+        };
+    }
+    Response::new(
+        200,
+        "application/json",
+        json!({"results": results}).to_string(),
+    )
+}
+
+/// Who a write is recorded as: the API key's name or the JWT's `sub`.
+fn actor(request: &Request) -> Option<&str> {
+    request
+        .principal
+        .as_ref()
+        .map(|principal| principal.name.as_str())
+}
+
+fn store_error(e: String) -> Response {
+    error!("Person store error: {}", e);
+    Response::text(500, "Server error")
+}
+
+fn get_person(store: &dyn PersonStore, request: &Request) -> Response {
+    let Some(id) = person_id(request) else {
+        return Response::text(400, "Invalid ID");
+    };
+    match store.get(id) {
+        Ok(Some(person)) => Response::text(
+            200,
+            format!(
+                "ID: {}, Name: {}, Email: {}",
+                person.id, person.name, person.email
+            ),
+        ),
+        Ok(None) => Response::text(404, "Person not found"),
+        Err(e) => store_error(e),
+    }
+}
+
+fn create_person(
+    store: &dyn PersonStore,
+    config: &ValidationConfig,
+    request: &Request,
+) -> Response {
+    let person = match new_person(request, config) {
+        Ok(person) => person,
+        Err(response) => return response,
+    };
+    match store.create(&person, actor(request)) {
+        Ok(id) => Response::text(201, format!("Person created with ID: {}", id)),
+        Err(e) => store_error(e),
+    }
+}
+
+fn update_person(
+    store: &dyn PersonStore,
+    config: &ValidationConfig,
+    request: &Request,
+) -> Response {
+    let Some(id) = person_id(request) else {
+        return Response::text(400, "Invalid ID");
+    };
+    let person = match new_person(request, config) {
+        Ok(person) => person,
+        Err(response) => return response,
+    };
+    match store.update(id, &person, actor(request)) {
+        Ok(true) => Response::text(200, "Person updated"),
+        Ok(false) => Response::text(404, "Person not found"),
+        Err(e) => store_error(e),
+    }
+}
+
+fn export_persons(pool: &Pool, request: &Request) -> Response {
+    let params = request.query_params();
+    let (format, content_type, extension) = match params.get("format").map(String::as_str) {
+        None | Some("csv") => (OutputFormat::Csv, "text/csv", "csv"),
+        Some("ndjson") => (OutputFormat::Ndjson, "application/x-ndjson", "ndjson"),
+        Some(other) => return Response::text(400, format!("Unknown format: {}", other)),
+    };
+    // Checked out before the headers go out so that an unreachable database
+    // is still reported with a status code.
+    let mut conn = match metrics::global().get_conn(pool) {
+        Ok(conn) => conn,
+        Err(e) => return store_error(e.to_string()),
+    };
+    Response::stream(200, content_type, move |writer| {
+        let rows = export::export_persons(&mut conn, format, writer).map_err(io::Error::other)?;
+        info!("Exported {} rows", rows);
+        Ok(())
+    })
+    .with_header(
+        "Content-Disposition",
+        format!("attachment; filename=\"persons.{}\"", extension),
+    )
+}
+
+/// `?format=` if given, else NDJSON when the body says so, else CSV.
+fn import_format(request: &Request) -> Result<OutputFormat, String> {
+    match request.query_params().get("format").map(String::as_str) {
+        Some("csv") => Ok(OutputFormat::Csv),
+        Some("ndjson") => Ok(OutputFormat::Ndjson),
+        Some(other) => Err(format!("Unknown format: {}", other)),
+        None => match request.header("content-type") {
+            Some(content_type) if content_type.starts_with("application/x-ndjson") => {
+                Ok(OutputFormat::Ndjson)
+            }
+            _ => Ok(OutputFormat::Csv),
+        },
+    }
+}
+
+fn import_persons(pool: &Pool, config: &ValidationConfig, request: &Request) -> Response {
+    let format = match import_format(request) {
+        Ok(format) => format,
+        Err(e) => return Response::text(400, e),
+    };
+    let strategy = match InsertStrategy::from_params(&request.query_params()) {
+        Ok(strategy) => strategy,
+        Err(e) => return Response::text(400, e),
+    };
+    let records = match import::parse(&request.body, format, &config.required) {
+        Ok(records) => records,
+        Err(e) => return Response::text(400, e),
+    };
+    let mut conn = match metrics::global().get_conn(pool) {
+        Ok(conn) => conn,
+        Err(e) => return store_error(e.to_string()),
+    };
+    let report = match import::import(&mut conn, records, strategy, config, actor(request)) {
+        Ok(report) => report,
+        Err(e) => return store_error(e),
+    };
+    info!(
+        "Imported {} persons, rejected {}",
+        report.accepted, report.rejected
+    );
+    let errors: Vec<Value> = report
+        .errors
+        .iter()
+        .map(|e| {
+            if e.fields.is_empty() {
+                json!({"line": e.line, "error": e.error})
+            } else {
+                json!({
+                    "line": e.line,
+                    "error": e.error,
+                    "errors": validation::errors_json(&e.fields),
+                })
+            }
+        })
+        .collect();
+    let body = json!({
+        "accepted": report.accepted,
+        "rejected": report.rejected,
+        "errors": errors,
+    });
+    Response::new(200, "application/json", body.to_string())
+}
+
+/// The generator `params` ask for and the seed it uses, so that a run
+/// without `seed` can still be repeated.
+fn row_generator(
+    config: &Config,
+    params: &HashMap<String, String>,
+) -> Result<(Arc<dyn RowGenerator>, u64), String> {
+    let seed = match params.get("seed").map(|s| s.parse::<u64>()) {
+        Some(Ok(seed)) => seed,
+        Some(Err(_)) => return Err("Invalid seed parameter".to_string()),
+        None => DataGenerator::random_seed(),
+    };
+    let table = params.get("table").map(String::as_str).unwrap_or("person");
+    let generator =
+        generation_spec::generator_for(config.populate.spec_file.as_deref(), table, seed)?;
+    Ok((generator, seed))
+}
+
+fn populate(pool: &Pool, config: Arc<Config>, request: &Request, mode: PopulateMode) -> Response {
+    let params = request.query_params();
+    let count = match params.get("count").and_then(|c| c.parse::<u32>().ok()) {
+        Some(count) => count,
+        None => return Response::text(400, "Missing or invalid count parameter"),
+    };
+    let strategy = match InsertStrategy::from_params(&params) {
+        Ok(strategy) => strategy,
+        Err(e) => return Response::text(400, e),
+    };
+    let (generator, seed) = match row_generator(&config, &params) {
+        Ok(generator) => generator,
+        Err(e) => return Response::text(400, e),
+    };
+    if let Err(e) = generator.check_capacity(count) {
+        return Response::text(400, e);
+    }
+    let strategy = strategy.for_columns(generator.columns().len());
+
+    match populate::populate(
+        pool.clone(),
+        config,
+        Arc::clone(&generator),
+        count,
+        mode,
+        strategy,
+    ) {
+        Ok(duration) => Response::text(
+            200,
+            format!(
+                "Successfully populated {} {} records in {:?} using {} (seed {})",
+                count,
+                generator.table(),
+                duration,
+                strategy,
+                seed
+            ),
+        ),
+        Err(e) => Response::text(500, format!("Failed to populate records: {}", e)),
+    }
+}
@@ -0,0 +1,42 @@
+use mysql::Value;
+use std::sync::mpsc::Sender;
+use tracing::error;
+
+pub type Row = Vec<Value>;
+
+/// Source of synthetic rows for one table. Row `index` must always produce
+/// the same values, whichever generator thread asks for it.
+pub trait RowGenerator: Send + Sync {
+    fn table(&self) -> &str;
+
+    fn columns(&self) -> &[String];
+
+    fn row(&self, index: u64) -> Row;
+
+    /// Largest number of rows that can be generated without breaking a
+    /// uniqueness constraint, if there is one.
+    fn capacity(&self) -> Option<u64> {
+        None
+    }
+
+    fn check_capacity(&self, count: u32) -> Result<(), String> {
+        match self.capacity() {
+            Some(capacity) if count as u64 > capacity => Err(format!(
+                "Unique columns of {} allow at most {} rows",
+                self.table(),
+                capacity
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn generate(&self, count: u32, start_id: u32, tx: Sender<Vec<Row>>) {
+        let rows: Vec<Row> = (start_id..start_id + count)
+            .map(|index| self.row(index as u64))
+            .collect();
+        let send = tx.send(rows);
+        if let Err(e) = send {
+            error!("Failed to send generated data: {}", e);
+        }
+    }
+}
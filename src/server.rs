@@ -1,271 +1,507 @@
-use crate::config::{self, Config};
-use crate::data_inserter::DataInserter;
-use crate::data_inserter_with_tokio::DataInserterWithTokio;
-use crate::request::Request;
-use crate::server_state::ServerState;
+use crate::access_log::{AccessEntry, AccessLog};
+use crate::auth::Authenticator;
+use crate::compression;
+use crate::config::{Config, PlainHttp};
+use crate::cors;
+use crate::http_rate_limit::{HttpRateLimiter, RateLimitDecision};
+use crate::idempotency::IdempotencyStore;
+use crate::logging;
+use crate::metrics;
+use crate::request::{ReadError, Request};
+use crate::response::Response;
+use crate::router::Router;
+use crate::routes;
+use crate::store::{MySqlPersonStore, PersonStore};
+use crate::tls::TlsAcceptor;
+use arc_swap::ArcSwap;
 use mysql::Pool;
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
-use std::sync::{Arc, Mutex};
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
-use tokio::runtime::Runtime;
+use std::time::{Duration, Instant};
+use tracing::field::Empty;
+use tracing::{Span, debug, error, info, info_span, warn};
 
+/// The HTTP server. Clones share the router, config and rate limiter.
+#[derive(Clone)]
 pub struct Server {
-    state: Arc<Mutex<ServerState>>,
-    rt: Runtime,
+    router: Arc<Router>,
+    /// Swapped as a whole by `reload`; requests and populate jobs keep the
+    /// snapshot they started with.
+    config: Arc<ArcSwap<Config>>,
+    rate_limiter: Arc<ArcSwap<HttpRateLimiter>>,
+    access_log: Arc<ArcSwap<AccessLog>>,
+    authenticator: Arc<ArcSwap<Authenticator>>,
+    idempotency: Arc<IdempotencyStore>,
+    lifecycle: Arc<Lifecycle>,
+    /// Set by `run` when `[tls]` is enabled.
+    tls: Arc<OnceLock<TlsAcceptor>>,
+}
+
+/// What a listener does with the connections it accepts.
+#[derive(Clone, Copy)]
+enum Listener {
+    Http,
+    /// Plain HTTP that only redirects to the HTTPS listener.
+    Redirect,
+    Https,
+}
+
+/// Shutdown state shared by a `Server`'s clones.
+#[derive(Default)]
+pub struct Lifecycle {
+    draining: AtomicBool,
+    stopped: AtomicBool,
+    in_flight: AtomicUsize,
+    local_addrs: Mutex<Vec<SocketAddr>>,
+}
+
+impl Lifecycle {
+    /// Whether a shutdown has started; `/readyz` then reports not ready.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+}
+
+/// Counts a request as in flight until dropped.
+struct InFlight<'a>(&'a AtomicUsize);
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Assembles a `Server` from a config plus whichever of the built-in routes
+/// and custom routes it should serve.
+pub struct ServerBuilder {
     config: Arc<Config>,
+    pool: Option<Pool>,
+    store: Option<Arc<dyn PersonStore>>,
+    routes: Router,
+}
+
+impl ServerBuilder {
+    /// Database for `/populate`, `/persons/export`, `/persons/import` and,
+    /// unless `store` is set, `/person`.
+    pub fn pool(mut self, pool: Pool) -> Self {
+        self.pool = Some(pool);
+        self
+    }
+
+    /// Serves `/person` from `store` instead of the pool's `person` table.
+    pub fn store(mut self, store: impl PersonStore + 'static) -> Self {
+        self.store = Some(Arc::new(store));
+        self
+    }
+
+    /// Extra routes, tried before the built-in ones.
+    pub fn routes(mut self, routes: Router) -> Self {
+        self.routes = self.routes.merge(routes);
+        self
+    }
+
+    /// Fails when the JWT keys named by `[auth.jwt]` cannot be loaded.
+    pub fn build(self) -> Result<Server, String> {
+        let authenticator = Authenticator::new(&self.config.auth)?;
+        let config = Arc::new(ArcSwap::new(Arc::clone(&self.config)));
+        let store = self.store.or_else(|| {
+            let pool = self.pool.clone()?;
+            Some(Arc::new(MySqlPersonStore::new(pool)) as Arc<dyn PersonStore>)
+        });
+
+        let lifecycle = Arc::new(Lifecycle::default());
+        let mut router = self
+            .routes
+            .merge(routes::metrics_routes())
+            .merge(routes::health_routes(
+                self.pool.clone(),
+                Arc::clone(&lifecycle),
+            ));
+        if let Some(store) = store {
+            router = router.merge(routes::person_routes(store, Arc::clone(&config)));
+        }
+        if let Some(pool) = self.pool {
+            router = router
+                .merge(routes::export_routes(pool.clone(), Arc::clone(&config)))
+                .merge(routes::populate_routes(pool, Arc::clone(&config)));
+        }
+        Ok(Server {
+            router: Arc::new(router),
+            rate_limiter: Arc::new(ArcSwap::from_pointee(HttpRateLimiter::new(
+                &self.config.http_rate_limit,
+            ))),
+            access_log: Arc::new(ArcSwap::from_pointee(AccessLog::new(
+                &self.config.access_log,
+            ))),
+            authenticator: Arc::new(ArcSwap::from_pointee(authenticator)),
+            idempotency: Arc::new(IdempotencyStore::new()),
+            config,
+            lifecycle,
+            tls: Arc::new(OnceLock::new()),
+        })
+    }
 }
 
 impl Server {
-    pub fn new(pool: Pool, config: Arc<Config>) -> Self {
-        Server {
-            state: Arc::new(Mutex::new(ServerState::new(pool))),
-            rt: Runtime::new().unwrap(),
+    /// A server with every built-in route backed by `pool`.
+    pub fn new(pool: Pool, config: Arc<Config>) -> Result<Self, String> {
+        Server::builder(config).pool(pool).build()
+    }
+
+    pub fn builder(config: Arc<Config>) -> ServerBuilder {
+        ServerBuilder {
             config,
+            pool: None,
+            store: None,
+            routes: Router::new(),
         }
     }
 
-    pub fn handle_request(&self, request: Request) -> (&'static str, String) {
-        match (request.method.as_str(), request.path.as_str()) {
-            ("GET", path) if path.starts_with("/person/") => {
-                let id_str = path.strip_prefix("/person/").unwrap_or("");
-                match id_str.parse::<u32>() {
-                    Ok(id) => {
-                        let state = self.state.lock().unwrap();
-                        match state.get_person(id) {
-                            Some(person) => (
-                                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n",
-                                format!(
-                                    "ID: {}, Name: {}, Age: {}",
-                                    person.id, person.name, person.email
-                                ),
-                            ),
-                            None => (
-                                "HTTP/1.1 404 NOT FOUND\r\n\r\n",
-                                "Person not found".to_string(),
-                            ),
-                        }
-                    }
-                    Err(_) => ("HTTP/1.1 400 BAD REQUEST\r\n\r\n", "Invalid ID".to_string()),
-                }
+    pub fn config(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    /// Applies a newly loaded config unless it changes a key that needs a
+    /// restart. Rate limiter state is reset and the access log reopened when
+    /// their sections changed; the TLS certificate and JWT keys are always
+    /// re-read.
+    pub fn reload(&self, config: Config) -> Result<Vec<String>, String> {
+        let current = self.config.load_full();
+        let changes = current.changes(&config);
+        let restart = Config::restart_required(&changes);
+        if !restart.is_empty() {
+            return Err(format!(
+                "{} cannot change without a restart",
+                restart.join(", ")
+            ));
+        }
+        // Everything that can fail runs before anything is swapped, so a
+        // rejected reload leaves the server as it was.
+        let certificate = match self.tls.get() {
+            Some(tls) => Some((tls, tls.load(&config.tls)?)),
+            None => None,
+        };
+        let authenticator = Authenticator::new(&config.auth)?;
+        if config.log != current.log {
+            logging::set_level(&config.log.level)?;
+        }
+        if let Some((tls, key)) = certificate {
+            tls.install(key);
+        }
+        if config.http_rate_limit != current.http_rate_limit {
+            self.rate_limiter
+                .store(Arc::new(HttpRateLimiter::new(&config.http_rate_limit)));
+        }
+        self.authenticator.store(Arc::new(authenticator));
+        if config.access_log != current.access_log {
+            self.access_log
+                .store(Arc::new(AccessLog::new(&config.access_log)));
+        }
+        self.config.store(Arc::new(config));
+        Ok(changes)
+    }
+
+    /// Re-reads the TLS certificate and key named by the current config.
+    /// Does nothing unless `run` started an HTTPS listener.
+    pub fn reload_certificates(&self) -> Result<(), String> {
+        match self.tls.get() {
+            Some(tls) => tls.reload(&self.config.load().tls),
+            None => Ok(()),
+        }
+    }
+
+    /// Reports not ready for `server.drain_secs`, then makes `run` stop
+    /// accepting connections and return once requests in flight finish.
+    /// Blocks for the drain period.
+    pub fn shutdown(&self) {
+        if self.lifecycle.draining.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let drain = self.config.load().server.drain();
+        info!("Draining for {:?} before shutting down", drain);
+        thread::sleep(drain);
+        self.lifecycle.stopped.store(true, Ordering::SeqCst);
+
+        // Wake the accept loops so they see the stop flag.
+        let addrs = self
+            .lifecycle
+            .local_addrs
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        for mut addr in addrs {
+            if addr.ip().is_unspecified() {
+                addr.set_ip(match addr {
+                    SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+                    SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+                });
             }
-            ("POST", "/person") => {
-                let params = request.parse_body();
-                let name = match params.get("name") {
-                    Some(name) => name.clone(),
-                    None => {
-                        return (
-                            "HTTP/1.1 400 BAD REQUEST\r\n\r\n",
-                            "Missing name".to_string(),
-                        );
-                    }
+            let _ = TcpStream::connect(addr);
+        }
+    }
+
+    pub fn handle_request(&self, request: &Request) -> Response {
+        self.router.handle(request)
+    }
+
+    /// Reads one plain HTTP request from `stream`, applies the rate limit
+    /// and writes the response.
+    pub fn handle_client(&self, stream: TcpStream) {
+        self.handle_connection(stream, Listener::Http);
+    }
+
+    fn handle_connection(&self, mut stream: TcpStream, listener: Listener) {
+        let _connection = metrics::global().connection();
+        self.lifecycle.in_flight.fetch_add(1, Ordering::SeqCst);
+        let _in_flight = InFlight(&self.lifecycle.in_flight);
+        let config = self.config.load();
+        if let Err(e) = stream
+            .set_read_timeout(Some(config.server.read_timeout()))
+            .and_then(|_| stream.set_write_timeout(Some(config.server.write_timeout())))
+        {
+            error!("Failed to set connection timeouts: {}", e);
+        }
+        let peer_addr = stream.peer_addr().ok().map(|addr| addr.ip());
+        match listener {
+            Listener::Https => {
+                let Some(tls) = self.tls.get() else {
+                    return;
                 };
-                let age = match params.get("age").and_then(|age| age.parse::<u32>().ok()) {
-                    Some(age) => age,
-                    None => {
-                        return (
-                            "HTTP/1.1 400 BAD REQUEST\r\n\r\n",
-                            "Invalid or missing age".to_string(),
-                        );
+                let mut stream = match tls.accept(stream) {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        error!("Failed to start TLS session: {}", e);
+                        return;
                     }
                 };
+                self.exchange(&mut stream, peer_addr, listener);
+                stream.conn.send_close_notify();
+                let _ = stream.flush();
+            }
+            _ => self.exchange(&mut stream, peer_addr, listener),
+        }
+    }
 
-                let mut state = self.state.lock().unwrap();
-                let id = state.add_person(name, age);
-                (
-                    "HTTP/1.1 201 CREATED\r\nContent-Type: text/plain\r\n\r\n",
-                    format!("Person created with ID: {}", id),
-                )
+    /// Answers CORS preflights, which carry no credentials, then authorizes
+    /// and routes everything else.
+    fn respond(&self, request: &mut Request) -> Response {
+        let config = self.config.load();
+        if config.cors.enabled && cors::is_preflight(request) {
+            let methods = self.router.methods_for(request.path_without_query());
+            if !methods.is_empty()
+                && let Some(response) = cors::preflight(&config.cors, request, &methods)
+            {
+                return response;
             }
-            ("PUT", path) if path.starts_with("/person/") => {
-                let id_str = path.strip_prefix("/person/").unwrap_or("");
-                match id_str.parse::<u32>() {
-                    Ok(id) => {
-                        let params = request.parse_body();
-                        let name = match params.get("name") {
-                            Some(name) => name.clone(),
-                            None => {
-                                return (
-                                    "HTTP/1.1 400 BAD REQUEST\r\n\r\n",
-                                    "Missing name".to_string(),
-                                );
-                            }
-                        };
-                        let age = match params.get("age").and_then(|age| age.parse::<u32>().ok()) {
-                            Some(age) => age,
-                            None => {
-                                return (
-                                    "HTTP/1.1 400 BAD REQUEST\r\n\r\n",
-                                    "Invalid or missing age".to_string(),
-                                );
-                            }
-                        };
+        }
+        self.authorize_and_handle(request)
+    }
 
-                        let mut state = self.state.lock().unwrap();
-                        if state.update_person(id, name, age) {
-                            (
-                                "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n",
-                                "Person updated".to_string(),
-                            )
-                        } else {
-                            (
-                                "HTTP/1.1 404 NOT FOUND\r\n\r\n",
-                                "Person not found".to_string(),
-                            )
-                        }
-                    }
-                    Err(_) => ("HTTP/1.1 400 BAD REQUEST\r\n\r\n", "Invalid ID".to_string()),
+    /// Routes the request once its API key is known to carry the role the
+    /// route needs.
+    fn authorize_and_handle(&self, request: &mut Request) -> Response {
+        match self.authenticator.load().check(request) {
+            Ok(principal) => {
+                if let Some(principal) = &principal {
+                    Span::current().record("principal", principal.name.as_str());
                 }
+                request.principal = principal;
+                let config = self.config.load();
+                self.idempotency.handle(&config.idempotency, request, || {
+                    self.handle_request(request)
+                })
             }
-            ("POST", path) if path.starts_with("/populate") => {
-                let query = path.split('?').nth(1).unwrap_or("");
-                let params: std::collections::HashMap<String, String> = query
-                    .split('&')
-                    .filter_map(|pair| {
-                        let kv: Vec<&str> = pair.splitn(2, '=').collect();
-                        if kv.len() == 2 {
-                            Some((kv[0].to_string(), kv[1].to_string()))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-                let count = match params.get("count").and_then(|c| c.parse::<u32>().ok()) {
-                    Some(count) => count,
-                    None => {
-                        return (
-                            "HTTP/1.1 400 BAD REQUEST\r\n\r\n",
-                            "Missing or invalid count parameter".to_string(),
-                        );
-                    }
-                };
+            Err(response) => {
+                debug!("Refused with {}", response.status);
+                response
+            }
+        }
+    }
 
-                let state = self.state.lock();
-                let state = match state {
-                    Ok(state) => state,
-                    Err(e) => {
-                        eprintln!("Failed to lock server state: {}", e);
-                        return (
-                            "HTTP/1.1 500 INTERNAL SERVER ERROR\r\n\r\n",
-                            "Server error".to_string(),
-                        );
-                    }
-                };
+    /// Permanent redirect to the same path on the HTTPS listener, keeping
+    /// the host the client asked for.
+    fn redirect(&self, request: &Request) -> Response {
+        let config = self.config.load();
+        let host = match request.header("host") {
+            Some(host) if host.starts_with('[') => host.split_inclusive(']').next().unwrap_or(host),
+            Some(host) => host.split(':').next().unwrap_or(host),
+            None => config.server.host.as_str(),
+        };
+        let location = match config.tls.port {
+            443 => format!("https://{}{}", host, request.path),
+            port => format!("https://{}:{}{}", host, port, request.path),
+        };
+        Response::text(308, location.clone()).with_header("Location", location)
+    }
 
-                let inserter =
-                    DataInserterWithTokio::new(state.pool.clone(), Arc::clone(&self.config));
-                match self.rt.block_on(inserter.populate(count)) {
-                    Ok(duration) => (
-                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n",
-                        format!("Successfully populated {} records in {:?}", count, duration),
-                    ),
-                    Err(e) => (
-                        "HTTP/1.1 500 INTERNAL SERVER ERROR\r\n\r\n",
-                        format!("Failed to populate records: {}", e),
-                    ),
+    fn exchange<S: Read + Write>(
+        &self,
+        stream: &mut S,
+        peer_addr: Option<IpAddr>,
+        listener: Listener,
+    ) {
+        let (mut request, rejected) = match Request::read(stream, &self.config.load().server) {
+            Ok(mut request) => {
+                request.peer_addr = peer_addr;
+                (Some(request), None)
+            }
+            Err(ReadError::Rejected(response)) => (None, Some(response)),
+            Err(ReadError::Io(e)) => {
+                error!("Failed to read from stream: {}", e);
+                return;
+            }
+        };
+        let start = Instant::now();
+        // Everything logged while handling the request, including populate
+        // threads it starts, carries its id.
+        let span = match &request {
+            Some(request) => info_span!(
+                "request",
+                id = %request.id,
+                method = %request.method,
+                path = %request.path,
+                principal = Empty
+            ),
+            None => Span::none(),
+        };
+        let _entered = span.enter();
+        let response = match &mut request {
+            None => rejected.unwrap_or_else(|| Response::text(400, "Bad Request")),
+            Some(request) => match self
+                .rate_limiter
+                .load()
+                .check(request, &self.authenticator.load())
+            {
+                RateLimitDecision::Limited { limit, retry_after } => {
+                    RateLimitDecision::too_many_requests(limit, retry_after)
                 }
+                decision => decision.apply(match listener {
+                    Listener::Redirect => self.redirect(request),
+                    _ => self.respond(request),
+                }),
             }
-            ("POST", path) if path.starts_with("/populate2") => {
-                let query = path.split('?').nth(1).unwrap_or("");
-                let params: std::collections::HashMap<String, String> = query
-                    .split('&')
-                    .filter_map(|pair| {
-                        let kv: Vec<&str> = pair.splitn(2, '=').collect();
-                        if kv.len() == 2 {
-                            Some((kv[0].to_string(), kv[1].to_string()))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
-                let count = match params.get("count").and_then(|c| c.parse::<u32>().ok()) {
-                    Some(count) => count,
-                    None => {
-                        return (
-                            "HTTP/1.1 400 BAD REQUEST\r\n\r\n",
-                            "Missing or invalid count parameter".to_string(),
-                        );
-                    }
-                };
+            .with_header("X-Request-Id", &request.id),
+        };
+        let config = self.config.load();
+        let response = match &request {
+            Some(request) if config.cors.enabled => cors::apply(&config.cors, request, response),
+            _ => response,
+        };
+        let mut response = match &request {
+            Some(request) if config.compression.enabled => {
+                compression::apply(&config.compression, request, response)
+            }
+            _ => response,
+        };
+        let sent = match response.write_to(stream) {
+            Ok(sent) => sent,
+            Err(e) => {
+                error!("Failed to write response: {}", e);
+                0
+            }
+        };
+        let latency = start.elapsed();
+        let (route, method) = match (&request, listener) {
+            (Some(request), Listener::Redirect) => ("https_redirect", request.method.as_str()),
+            (Some(request), _) => (
+                self.router.pattern_for(request).unwrap_or("unmatched"),
+                request.method.as_str(),
+            ),
+            (None, _) => ("unparsed", "-"),
+        };
+        metrics::global().record_request(route, method, response.status, latency);
+        let mut entry = AccessEntry::new(peer_addr, request.as_ref(), &response, latency);
+        // Streamed bodies are only counted as they are sent.
+        entry.bytes = sent as usize;
+        self.access_log.load().record(&entry);
+    }
 
-                let state = self.state.lock();
-                let state = match state {
-                    Ok(state) => state,
-                    Err(e) => {
-                        eprintln!("Failed to lock server state: {}", e);
-                        return (
-                            "HTTP/1.1 500 INTERNAL SERVER ERROR\r\n\r\n",
-                            "Server error".to_string(),
-                        );
-                    }
-                };
+    /// Opens the listeners the config asks for: plain HTTP on
+    /// `server.port` and, with `[tls]` enabled, HTTPS on `tls.port`. Accepts
+    /// connections, one thread per connection, until `shutdown` is called.
+    pub fn run(&self) -> io::Result<()> {
+        let config = self.config.load_full();
+        let mut listeners = Vec::new();
+        if config.tls.enabled {
+            let tls = TlsAcceptor::new(&config.tls)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let _ = self.tls.set(tls);
+            listeners.push((config.tls.port, Listener::Https));
+        }
+        let plain = match config.tls.http {
+            _ if !config.tls.enabled => Some(Listener::Http),
+            PlainHttp::Serve => Some(Listener::Http),
+            PlainHttp::Redirect => Some(Listener::Redirect),
+            PlainHttp::Off => None,
+        };
+        if let Some(plain) = plain {
+            listeners.push((config.server.port, plain));
+        }
 
-                let inserter = DataInserter::new(state.pool.clone());
-                match inserter.populate(count) {
-                    Ok(_) => (
-                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\n\r\n",
-                        format!("Successfully populated {} records", count),
-                    ),
-                    Err(e) => (
-                        "HTTP/1.1 500 INTERNAL SERVER ERROR\r\n\r\n",
-                        format!("Failed to populate records: {}", e),
-                    ),
-                }
+        let mut bound = Vec::new();
+        for (port, kind) in listeners {
+            let addr = format!("{}:{}", config.server.host, port);
+            let listener = TcpListener::bind(&addr).map_err(|e| {
+                io::Error::new(e.kind(), format!("Failed to listen on {}: {}", addr, e))
+            })?;
+            if let Ok(local_addr) = listener.local_addr() {
+                self.lifecycle
+                    .local_addrs
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .push(local_addr);
             }
-            _ => (
-                "HTTP/1.1 404 NOT FOUND\r\n\r\n",
-                "404 - Endpoint not found".to_string(),
-            ),
+            match kind {
+                Listener::Http => info!("Server running on http://{}", addr),
+                Listener::Redirect => info!("Redirecting http://{} to HTTPS", addr),
+                Listener::Https => info!("Server running on https://{}", addr),
+            }
+            bound.push((listener, kind));
         }
-    }
 
-    pub fn handle_client(&self, mut stream: TcpStream) {
-        let mut buffer = [0; 1024];
-        match stream.read(&mut buffer) {
-            Ok(_) => {
-                let request_str = String::from_utf8_lossy(&buffer[..]);
-                let request = match Request::parse(&request_str) {
-                    Some(req) => req,
-                    None => {
-                        let response = "HTTP/1.1 400 BAD REQUEST\r\n\r\nBad Request";
-                        stream.write_all(response.as_bytes()).unwrap();
-                        stream.flush().unwrap();
-                        return;
-                    }
-                };
-                let (status_line, contents) = self.handle_request(request);
-                let response = format!("{}{}", status_line, contents);
-                stream.write_all(response.as_bytes()).unwrap();
-                stream.flush().unwrap();
+        let accept_loops: Vec<_> = bound
+            .into_iter()
+            .map(|(listener, kind)| {
+                let server = self.clone();
+                thread::spawn(move || server.accept(listener, kind))
+            })
+            .collect();
+        for accept_loop in accept_loops {
+            let _ = accept_loop.join();
+        }
+
+        let deadline = Instant::now() + self.config.load().server.shutdown_timeout();
+        while self.lifecycle.in_flight.load(Ordering::SeqCst) > 0 {
+            if Instant::now() >= deadline {
+                warn!(
+                    "Stopping with {} requests still in flight",
+                    self.lifecycle.in_flight.load(Ordering::SeqCst)
+                );
+                break;
             }
-            Err(e) => eprintln!("Failed to read from stream: {}", e),
+            thread::sleep(Duration::from_millis(50));
         }
+        info!("Server stopped");
+        Ok(())
     }
 
-    pub fn run(&self, addr: &str) {
-        let listener = TcpListener::bind(addr).unwrap();
-        println!("Server running on http://{}", addr);
-
+    fn accept(&self, listener: TcpListener, kind: Listener) {
         for stream in listener.incoming() {
+            if self.lifecycle.stopped.load(Ordering::SeqCst) {
+                break;
+            }
             match stream {
                 Ok(stream) => {
                     let server = self.clone();
                     thread::spawn(move || {
-                        server.handle_client(stream);
+                        server.handle_connection(stream, kind);
                     });
                 }
-                Err(e) => eprintln!("Error: {}", e),
+                Err(e) => error!("Failed to accept connection: {}", e),
             }
         }
     }
 }
-
-impl Clone for Server {
-    fn clone(&self) -> Self {
-        Server {
-            state: Arc::clone(&self.state),
-            rt: Runtime::new().unwrap(),
-            config: Arc::clone(&self.config),
-        }
-    }
-}
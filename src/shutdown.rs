@@ -0,0 +1,46 @@
+use crate::server::Server;
+use std::process;
+use std::thread;
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{error, info, warn};
+
+/// Starts a graceful `Server::shutdown` on SIGTERM or SIGINT; a second
+/// signal exits immediately.
+pub fn watch(server: Server) {
+    thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async move {
+            let mut terminate = match signal(SignalKind::terminate()) {
+                Ok(terminate) => terminate,
+                Err(e) => {
+                    error!("Failed to listen for SIGTERM: {}", e);
+                    return;
+                }
+            };
+            let mut interrupt = match signal(SignalKind::interrupt()) {
+                Ok(interrupt) => interrupt,
+                Err(e) => {
+                    error!("Failed to listen for SIGINT: {}", e);
+                    return;
+                }
+            };
+
+            let name = tokio::select! {
+                _ = terminate.recv() => "SIGTERM",
+                _ = interrupt.recv() => "SIGINT",
+            };
+            info!("Received {}, shutting down", name);
+            thread::spawn(move || server.shutdown());
+
+            tokio::select! {
+                _ = terminate.recv() => {},
+                _ = interrupt.recv() => {},
+            }
+            warn!("Received a second signal, exiting immediately");
+            process::exit(1);
+        });
+    });
+}
@@ -0,0 +1,228 @@
+use crate::row_generator::Row;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use mysql::Value;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// File format produced by a `RowSink`.
+#[derive(Clone, Copy, Debug)]
+pub enum OutputFormat {
+    Csv,
+    Ndjson,
+    /// `.sql` dump of multi-row `INSERT` statements.
+    Sql {
+        rows_per_statement: usize,
+    },
+}
+
+impl OutputFormat {
+    pub fn parse(format: &str, rows_per_statement: usize) -> Result<Self, String> {
+        match format {
+            "csv" => Ok(OutputFormat::Csv),
+            "ndjson" | "jsonl" => Ok(OutputFormat::Ndjson),
+            "sql" if rows_per_statement > 0 => Ok(OutputFormat::Sql { rows_per_statement }),
+            "sql" => Err("rows_per_statement must be at least 1".to_string()),
+            other => Err(format!("Unknown output format: {}", other)),
+        }
+    }
+}
+
+/// Destination for generated rows; rows arrive in order, in batches.
+pub trait RowSink {
+    fn write_rows(&mut self, rows: &[Row]) -> io::Result<()>;
+
+    /// Flushes buffered output and finishes any compression stream.
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// Opens `path` for writing in `format`, gzip-compressed when `gzip` is set
+/// or the path ends in `.gz`.
+pub fn open_file_sink(
+    path: &str,
+    format: OutputFormat,
+    gzip: bool,
+    table: &str,
+    columns: &[String],
+) -> io::Result<Box<dyn RowSink>> {
+    let file = BufWriter::new(File::create(path)?);
+    let writer: Box<dyn FinishWrite> = if gzip || path.ends_with(".gz") {
+        Box::new(GzEncoder::new(file, Compression::default()))
+    } else {
+        Box::new(file)
+    };
+    new_sink(writer, format, table, columns)
+}
+
+pub fn new_sink<'a, W: FinishWrite + 'a>(
+    writer: W,
+    format: OutputFormat,
+    table: &str,
+    columns: &[String],
+) -> io::Result<Box<dyn RowSink + 'a>> {
+    Ok(match format {
+        OutputFormat::Csv => Box::new(CsvSink::new(writer, columns)?),
+        OutputFormat::Ndjson => Box::new(NdjsonSink {
+            writer,
+            columns: columns.to_vec(),
+        }),
+        OutputFormat::Sql { rows_per_statement } => Box::new(SqlSink {
+            writer,
+            insert: format!(
+                "INSERT INTO `{}` ({}) VALUES\n",
+                table,
+                columns
+                    .iter()
+                    .map(|column| format!("`{}`", column))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            rows_per_statement,
+            rows_in_statement: 0,
+        }),
+    })
+}
+
+/// `Write` that needs an explicit step to complete its output, such as a
+/// compression trailer.
+pub trait FinishWrite: Write {
+    fn finish_write(self: Box<Self>) -> io::Result<()>;
+}
+
+impl<W: Write> FinishWrite for BufWriter<W> {
+    fn finish_write(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl<W: Write> FinishWrite for GzEncoder<W> {
+    fn finish_write(self: Box<Self>) -> io::Result<()> {
+        (*self).finish()?.flush()
+    }
+}
+
+impl FinishWrite for Box<dyn FinishWrite> {
+    fn finish_write(self: Box<Self>) -> io::Result<()> {
+        (*self).finish_write()
+    }
+}
+
+/// A borrowed writer, such as a response body, that the caller finishes.
+impl FinishWrite for &mut dyn Write {
+    fn finish_write(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+/// Text form of a value; `None` for `NULL`.
+fn value_text(value: &Value) -> Option<String> {
+    match value {
+        Value::NULL => None,
+        Value::Bytes(bytes) => Some(String::from_utf8_lossy(bytes).into_owned()),
+        Value::Int(n) => Some(n.to_string()),
+        Value::UInt(n) => Some(n.to_string()),
+        Value::Float(n) => Some(n.to_string()),
+        Value::Double(n) => Some(n.to_string()),
+        other => Some(other.as_sql(true).trim_matches('\'').to_string()),
+    }
+}
+
+struct CsvSink<W: FinishWrite> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: FinishWrite> CsvSink<W> {
+    fn new(writer: W, columns: &[String]) -> io::Result<Self> {
+        let mut writer = csv::Writer::from_writer(writer);
+        writer.write_record(columns)?;
+        Ok(CsvSink { writer })
+    }
+}
+
+impl<W: FinishWrite> RowSink for CsvSink<W> {
+    fn write_rows(&mut self, rows: &[Row]) -> io::Result<()> {
+        for row in rows {
+            // CSV has no NULL; it becomes an empty field.
+            self.writer.write_record(
+                row.iter()
+                    .map(|value| value_text(value).unwrap_or_default()),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let writer = self.writer.into_inner().map_err(|e| e.into_error())?;
+        Box::new(writer).finish_write()
+    }
+}
+
+struct NdjsonSink<W: FinishWrite> {
+    writer: W,
+    columns: Vec<String>,
+}
+
+fn json_value(value: &Value) -> serde_json::Value {
+    match value {
+        Value::NULL => serde_json::Value::Null,
+        Value::Int(n) => (*n).into(),
+        Value::UInt(n) => (*n).into(),
+        Value::Float(n) => (*n).into(),
+        Value::Double(n) => (*n).into(),
+        other => value_text(other).into(),
+    }
+}
+
+impl<W: FinishWrite> RowSink for NdjsonSink<W> {
+    fn write_rows(&mut self, rows: &[Row]) -> io::Result<()> {
+        for row in rows {
+            let object: serde_json::Map<String, serde_json::Value> = self
+                .columns
+                .iter()
+                .cloned()
+                .zip(row.iter().map(json_value))
+                .collect();
+            serde_json::to_writer(&mut self.writer, &object)?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Box::new(self.writer).finish_write()
+    }
+}
+
+struct SqlSink<W: FinishWrite> {
+    writer: W,
+    insert: String,
+    rows_per_statement: usize,
+    rows_in_statement: usize,
+}
+
+impl<W: FinishWrite> RowSink for SqlSink<W> {
+    fn write_rows(&mut self, rows: &[Row]) -> io::Result<()> {
+        for row in rows {
+            if self.rows_in_statement == 0 {
+                self.writer.write_all(self.insert.as_bytes())?;
+            } else {
+                self.writer.write_all(b",\n")?;
+            }
+            let values: Vec<String> = row.iter().map(|value| value.as_sql(false)).collect();
+            write!(self.writer, "({})", values.join(", "))?;
+            self.rows_in_statement += 1;
+            if self.rows_in_statement == self.rows_per_statement {
+                self.writer.write_all(b";\n")?;
+                self.rows_in_statement = 0;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        if self.rows_in_statement > 0 {
+            self.writer.write_all(b";\n")?;
+        }
+        Box::new(self.writer).finish_write()
+    }
+}
@@ -0,0 +1,464 @@
+use crate::metrics;
+use crate::model::person::{NewPerson, Person};
+use mysql::{Pool, TxOpts, Value, params, prelude::*};
+use std::collections::{HashMap, HashSet};
+
+type PersonRow = (u32, String, String, String, String, String, String, u32);
+
+/// One item of a batch upsert: updates person `id` when it is given, else
+/// the person with the same email, else creates one.
+pub struct Upsert {
+    pub id: Option<u32>,
+    pub person: NewPerson,
+}
+
+/// What an `Upsert` did.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UpsertOutcome {
+    Created(u32),
+    Updated(u32),
+    /// The upsert named an `id` that does not exist.
+    NotFound,
+}
+
+/// Storage behind the `/person` routes. Implement it to serve people from
+/// something other than MySQL.
+pub trait PersonStore: Send + Sync {
+    fn get(&self, id: u32) -> Result<Option<Person>, String>;
+
+    /// Stores a new person and returns its id. `actor` names the
+    /// authenticated caller, when there is one.
+    fn create(&self, person: &NewPerson, actor: Option<&str>) -> Result<u32, String>;
+
+    /// Replaces a person's fields and bumps its version. Returns `false` when
+    /// there is no person with `id`.
+    fn update(&self, id: u32, person: &NewPerson, actor: Option<&str>) -> Result<bool, String>;
+
+    /// The people among `ids` that exist, in any order.
+    fn get_many(&self, ids: &[u32]) -> Result<Vec<Person>, String>;
+
+    /// Applies `upserts` together, all or none of them, and returns their
+    /// outcomes in order. No two upserts may share an id, and no two without
+    /// an id may share an email.
+    fn upsert_many(
+        &self,
+        upserts: &[Upsert],
+        actor: Option<&str>,
+    ) -> Result<Vec<UpsertOutcome>, String>;
+}
+
+/// `PersonStore` over the `person` table.
+pub struct MySqlPersonStore {
+    pool: Pool,
+}
+
+impl MySqlPersonStore {
+    pub fn new(pool: Pool) -> Self {
+        MySqlPersonStore { pool }
+    }
+}
+
+fn person_params(person: &NewPerson, actor: Option<&str>) -> Vec<(String, mysql::Value)> {
+    vec![
+        ("name".to_string(), person.name.clone().into()),
+        ("email".to_string(), person.email.clone().into()),
+        ("phone".to_string(), person.phone.clone().into()),
+        ("address".to_string(), person.address.clone().into()),
+        ("city".to_string(), person.city.clone().into()),
+        ("state".to_string(), person.state.clone().into()),
+        ("updated_by".to_string(), actor.into()),
+    ]
+}
+
+fn person_from_row((id, name, email, phone, address, city, state, version): PersonRow) -> Person {
+    Person {
+        id,
+        name,
+        email,
+        phone,
+        address,
+        city,
+        state,
+        version,
+    }
+}
+
+/// `?, ?, ...` for `count` values.
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+/// What each upsert does, given the ids among them that exist and the
+/// oldest person with each lowercased email. Creates are `Created(0)` until
+/// `number_created` fills in their ids.
+fn plan(
+    upserts: &[Upsert],
+    existing: &HashSet<u32>,
+    by_email: &HashMap<String, u32>,
+) -> Vec<UpsertOutcome> {
+    upserts
+        .iter()
+        .map(|upsert| match upsert.id {
+            Some(id) if existing.contains(&id) => UpsertOutcome::Updated(id),
+            Some(_) => UpsertOutcome::NotFound,
+            None => match by_email.get(&upsert.person.email.to_lowercase()) {
+                Some(&id) => UpsertOutcome::Updated(id),
+                None => UpsertOutcome::Created(0),
+            },
+        })
+        .collect()
+}
+
+/// Gives each `Created` outcome the id of the inserted row with its
+/// upsert's email, from the `(id, email)` pairs read back after the insert.
+fn number_created(
+    outcomes: &mut [UpsertOutcome],
+    upserts: &[Upsert],
+    rows: Vec<(u32, String)>,
+) -> Result<(), String> {
+    let mut by_email: HashMap<String, u32> = HashMap::new();
+    for (id, email) in rows {
+        by_email.entry(email.to_lowercase()).or_insert(id);
+    }
+    for (outcome, upsert) in outcomes.iter_mut().zip(upserts) {
+        if let UpsertOutcome::Created(_) = outcome {
+            let email = &upsert.person.email;
+            let id = by_email
+                .get(&email.to_lowercase())
+                .ok_or_else(|| format!("Inserted person with email {} not found", email))?;
+            *outcome = UpsertOutcome::Created(*id);
+        }
+    }
+    Ok(())
+}
+
+/// The parameters of the two statements an upsert runs, from its planned
+/// outcomes.
+struct Split<'a> {
+    /// `id` and then the fields, for each person updated.
+    updates: Vec<Value>,
+    /// The fields of each person created.
+    creates: Vec<Value>,
+    created_emails: Vec<&'a str>,
+}
+
+fn split<'a>(upserts: &'a [Upsert], outcomes: &[UpsertOutcome], actor: Option<&str>) -> Split<'a> {
+    let mut split = Split {
+        updates: Vec::new(),
+        creates: Vec::new(),
+        created_emails: Vec::new(),
+    };
+    for (upsert, outcome) in upserts.iter().zip(outcomes) {
+        let values = person_params(&upsert.person, actor)
+            .into_iter()
+            .map(|(_, value)| value);
+        match outcome {
+            UpsertOutcome::Updated(id) => {
+                split.updates.push((*id).into());
+                split.updates.extend(values);
+            }
+            UpsertOutcome::Created(_) => {
+                split.creates.extend(values);
+                split.created_emails.push(&upsert.person.email);
+            }
+            UpsertOutcome::NotFound => {}
+        }
+    }
+    split
+}
+
+/// Overwrites `rows` persons by id. The new values are read through the
+/// `new` row alias, as `VALUES(col)` is deprecated since MySQL 8.0.20.
+fn update_query(rows: usize) -> String {
+    format!(
+        "INSERT INTO person (id, name, email, phone, address, city, state, updated_by) \
+         VALUES {} AS new ON DUPLICATE KEY UPDATE name = new.name, email = new.email, \
+         phone = new.phone, address = new.address, city = new.city, state = new.state, \
+         updated_by = new.updated_by, version = person.version + 1",
+        vec![format!("({})", placeholders(8)); rows].join(", ")
+    )
+}
+
+fn create_query(rows: usize) -> String {
+    format!(
+        "INSERT INTO person (name, email, phone, address, city, state, updated_by) VALUES {}",
+        vec![format!("({})", placeholders(7)); rows].join(", ")
+    )
+}
+
+impl PersonStore for MySqlPersonStore {
+    fn get(&self, id: u32) -> Result<Option<Person>, String> {
+        let mut conn = metrics::global()
+            .get_conn(&self.pool)
+            .map_err(|e| e.to_string())?;
+        let person: Option<PersonRow> = conn
+            .exec_first(
+                "SELECT id, name, email, phone, address, city, state, version \
+                 FROM person WHERE id = :id",
+                params! { "id" => id },
+            )
+            .map_err(|e| e.to_string())?;
+
+        Ok(person.map(person_from_row))
+    }
+
+    fn create(&self, person: &NewPerson, actor: Option<&str>) -> Result<u32, String> {
+        let mut conn = metrics::global()
+            .get_conn(&self.pool)
+            .map_err(|e| e.to_string())?;
+        conn.exec_drop(
+            "INSERT INTO person (name, email, phone, address, city, state, updated_by) \
+             VALUES (:name, :email, :phone, :address, :city, :state, :updated_by)",
+            person_params(person, actor),
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(conn.last_insert_id() as u32)
+    }
+
+    fn update(&self, id: u32, person: &NewPerson, actor: Option<&str>) -> Result<bool, String> {
+        let mut conn = metrics::global()
+            .get_conn(&self.pool)
+            .map_err(|e| e.to_string())?;
+        let mut params = person_params(person, actor);
+        params.push(("id".to_string(), id.into()));
+        conn.exec_drop(
+            "UPDATE person SET name = :name, email = :email, phone = :phone, \
+             address = :address, city = :city, state = :state, updated_by = :updated_by, \
+             version = version + 1 \
+             WHERE id = :id",
+            params,
+        )
+        .map_err(|e| e.to_string())?;
+        Ok(conn.affected_rows() > 0)
+    }
+
+    fn get_many(&self, ids: &[u32]) -> Result<Vec<Person>, String> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = metrics::global()
+            .get_conn(&self.pool)
+            .map_err(|e| e.to_string())?;
+        conn.exec_map(
+            format!(
+                "SELECT id, name, email, phone, address, city, state, version \
+                 FROM person WHERE id IN ({})",
+                placeholders(ids.len())
+            ),
+            ids.to_vec(),
+            person_from_row,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    fn upsert_many(
+        &self,
+        upserts: &[Upsert],
+        actor: Option<&str>,
+    ) -> Result<Vec<UpsertOutcome>, String> {
+        if upserts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut conn = metrics::global()
+            .get_conn(&self.pool)
+            .map_err(|e| e.to_string())?;
+        let mut tx = conn
+            .start_transaction(TxOpts::default())
+            .map_err(|e| e.to_string())?;
+
+        // Lock the rows being updated so that none is deleted before the
+        // upsert below would re-insert it.
+        let ids: Vec<u32> = upserts.iter().filter_map(|upsert| upsert.id).collect();
+        let existing: HashSet<u32> = if ids.is_empty() {
+            HashSet::new()
+        } else {
+            tx.exec::<u32, _, _>(
+                format!(
+                    "SELECT id FROM person WHERE id IN ({}) FOR UPDATE",
+                    placeholders(ids.len())
+                ),
+                ids,
+            )
+            .map_err(|e| e.to_string())?
+            .into_iter()
+            .collect()
+        };
+        // Emails compare case-insensitively, as in the column's collation;
+        // the oldest person with an email is the one updated.
+        let emails: Vec<&str> = upserts
+            .iter()
+            .filter(|upsert| upsert.id.is_none())
+            .map(|upsert| upsert.person.email.as_str())
+            .collect();
+        let mut by_email: HashMap<String, u32> = HashMap::new();
+        if !emails.is_empty() {
+            let rows: Vec<(u32, String)> = tx
+                .exec(
+                    format!(
+                        "SELECT id, email FROM person WHERE email IN ({}) \
+                         ORDER BY id FOR UPDATE",
+                        placeholders(emails.len())
+                    ),
+                    emails,
+                )
+                .map_err(|e| e.to_string())?;
+            for (id, email) in rows {
+                by_email.entry(email.to_lowercase()).or_insert(id);
+            }
+        }
+
+        let mut outcomes = plan(upserts, &existing, &by_email);
+        let Split {
+            updates,
+            creates,
+            created_emails,
+        } = split(upserts, &outcomes, actor);
+
+        if !updates.is_empty() {
+            tx.exec_drop(update_query(updates.len() / 8), updates)
+                .map_err(|e| e.to_string())?;
+        }
+        if !creates.is_empty() {
+            tx.exec_drop(create_query(creates.len() / 7), creates)
+                .map_err(|e| e.to_string())?;
+            // The new ids are read back: with innodb_autoinc_lock_mode = 2 a
+            // multi-row INSERT need not get consecutive ones, only ones from
+            // LAST_INSERT_ID() up. Rows other transactions insert meanwhile are
+            // not visible here.
+            let first = tx.last_insert_id().unwrap_or(0);
+            let mut params: Vec<Value> = vec![first.into()];
+            params.extend(created_emails.iter().map(|email| Value::from(*email)));
+            let rows: Vec<(u32, String)> = tx
+                .exec(
+                    format!(
+                        "SELECT id, email FROM person WHERE id >= ? AND email IN ({}) ORDER BY id",
+                        placeholders(created_emails.len())
+                    ),
+                    params,
+                )
+                .map_err(|e| e.to_string())?;
+            number_created(&mut outcomes, upserts, rows)?;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(outcomes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upsert(id: Option<u32>, email: &str) -> Upsert {
+        Upsert {
+            id,
+            person: NewPerson {
+                name: "Ada Lovelace".to_string(),
+                email: email.to_string(),
+                phone: String::new(),
+                address: String::new(),
+                city: String::new(),
+                state: String::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn plan_updates_existing_ids_and_known_emails() {
+        let upserts = [
+            upsert(Some(3), "a@example.com"),
+            upsert(Some(4), "b@example.com"),
+            upsert(None, "C@Example.com"),
+            upsert(None, "d@example.com"),
+        ];
+        let existing = HashSet::from([3]);
+        let by_email = HashMap::from([("c@example.com".to_string(), 7)]);
+
+        assert_eq!(
+            plan(&upserts, &existing, &by_email),
+            [
+                UpsertOutcome::Updated(3),
+                UpsertOutcome::NotFound,
+                UpsertOutcome::Updated(7),
+                UpsertOutcome::Created(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn created_ids_follow_the_emails_not_the_insert_order() {
+        let upserts = [
+            upsert(None, "a@example.com"),
+            upsert(Some(3), "b@example.com"),
+            upsert(None, "C@example.com"),
+        ];
+        let mut outcomes = [
+            UpsertOutcome::Created(0),
+            UpsertOutcome::Updated(3),
+            UpsertOutcome::Created(0),
+        ];
+        // Not consecutive, as innodb_autoinc_lock_mode = 2 allows.
+        let rows = vec![
+            (40, "c@example.com".to_string()),
+            (52, "a@example.com".to_string()),
+        ];
+
+        number_created(&mut outcomes, &upserts, rows).unwrap();
+
+        assert_eq!(
+            outcomes,
+            [
+                UpsertOutcome::Created(52),
+                UpsertOutcome::Updated(3),
+                UpsertOutcome::Created(40),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_created_row_that_was_not_read_back_is_an_error() {
+        let upserts = [upsert(None, "a@example.com")];
+        let mut outcomes = [UpsertOutcome::Created(0)];
+
+        let error = number_created(&mut outcomes, &upserts, Vec::new()).unwrap_err();
+
+        assert!(error.contains("a@example.com"), "{}", error);
+    }
+
+    #[test]
+    fn split_sends_updates_and_creates_to_their_own_statements() {
+        let upserts = [
+            upsert(Some(3), "a@example.com"),
+            upsert(Some(4), "b@example.com"),
+            upsert(None, "c@example.com"),
+            upsert(None, "d@example.com"),
+        ];
+        let outcomes = [
+            UpsertOutcome::Updated(3),
+            UpsertOutcome::NotFound,
+            UpsertOutcome::Created(0),
+            UpsertOutcome::Updated(7),
+        ];
+
+        let split = split(&upserts, &outcomes, Some("etl"));
+
+        assert_eq!(split.updates.len(), 2 * 8);
+        assert_eq!(split.updates[0], Value::from(3u32));
+        assert_eq!(split.updates[2], Value::from("a@example.com"));
+        assert_eq!(split.updates[7], Value::from("etl"));
+        assert_eq!(split.updates[8], Value::from(7u32));
+        assert_eq!(split.updates[10], Value::from("d@example.com"));
+        assert_eq!(split.creates.len(), 7);
+        assert_eq!(split.creates[1], Value::from("c@example.com"));
+        assert_eq!(split.created_emails, ["c@example.com"]);
+    }
+
+    #[test]
+    fn updates_read_the_new_values_through_a_row_alias() {
+        let query = update_query(2);
+
+        assert!(query.contains("(?, ?, ?, ?, ?, ?, ?, ?), (?, ?, ?, ?, ?, ?, ?, ?) AS new"));
+        assert!(query.contains("name = new.name"));
+        assert!(query.contains("version = person.version + 1"));
+        assert!(!query.contains("VALUES("), "{}", query);
+    }
+}
@@ -0,0 +1,97 @@
+use crate::config::TlsConfig;
+use arc_swap::ArcSwap;
+use rustls::crypto::{CryptoProvider, ring};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{ServerConfig, ServerConnection, StreamOwned};
+use std::net::TcpStream;
+use std::sync::Arc;
+
+/// A TLS connection over an accepted socket. The handshake runs on the
+/// first read.
+pub type TlsStream = StreamOwned<ServerConnection, TcpStream>;
+
+fn load_certified_key(
+    config: &TlsConfig,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, String> {
+    let certs = CertificateDer::pem_file_iter(&config.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read {}: {}", config.cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificates in {}", config.cert_path));
+    }
+    let key = PrivateKeyDer::from_pem_file(&config.key_path)
+        .map_err(|e| format!("Failed to read {}: {}", config.key_path, e))?;
+    CertifiedKey::from_der(certs, key, provider).map_err(|e| {
+        format!(
+            "{} does not match {}: {}",
+            config.key_path, config.cert_path, e
+        )
+    })
+}
+
+/// Hands every handshake whichever certificate was loaded last.
+#[derive(Debug)]
+struct CertResolver(ArcSwap<CertifiedKey>);
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        Some(self.0.load_full())
+    }
+}
+
+/// Server side of TLS for the HTTPS listener. The certificate can be
+/// replaced while connections are being accepted; handshakes already under
+/// way keep the old one.
+pub struct TlsAcceptor {
+    config: Arc<ServerConfig>,
+    provider: Arc<CryptoProvider>,
+    resolver: Arc<CertResolver>,
+}
+
+impl TlsAcceptor {
+    pub fn new(config: &TlsConfig) -> Result<Self, String> {
+        let provider = Arc::new(ring::default_provider());
+        let resolver = Arc::new(CertResolver(ArcSwap::from_pointee(load_certified_key(
+            config, &provider,
+        )?)));
+        let mut server_config = ServerConfig::builder_with_provider(Arc::clone(&provider))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| e.to_string())?
+            .with_no_client_auth()
+            .with_cert_resolver(Arc::clone(&resolver) as Arc<dyn ResolvesServerCert>);
+        server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        Ok(TlsAcceptor {
+            config: Arc::new(server_config),
+            provider,
+            resolver,
+        })
+    }
+
+    /// Re-reads the certificate and key, keeping the current pair if either
+    /// fails to load.
+    pub fn reload(&self, config: &TlsConfig) -> Result<(), String> {
+        let key = self.load(config)?;
+        self.install(key);
+        Ok(())
+    }
+
+    /// Reads the certificate and key without serving them yet.
+    pub fn load(&self, config: &TlsConfig) -> Result<CertifiedKey, String> {
+        load_certified_key(config, &self.provider)
+    }
+
+    /// Serves `key` to connections accepted from now on.
+    pub fn install(&self, key: CertifiedKey) {
+        self.resolver.0.store(Arc::new(key));
+    }
+
+    pub fn accept(&self, stream: TcpStream) -> Result<TlsStream, String> {
+        let connection =
+            ServerConnection::new(Arc::clone(&self.config)).map_err(|e| e.to_string())?;
+        Ok(StreamOwned::new(connection, stream))
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::{PersonField, ValidationConfig};
+use crate::model::person::NewPerson;
+use crate::response::Response;
+use serde_json::{Value, json};
+
+/// A rule one field of a person broke.
+pub struct FieldError {
+    pub field: PersonField,
+    pub message: String,
+}
+
+fn value(person: &NewPerson, field: PersonField) -> &str {
+    match field {
+        PersonField::Name => &person.name,
+        PersonField::Email => &person.email,
+        PersonField::Phone => &person.phone,
+        PersonField::Address => &person.address,
+        PersonField::City => &person.city,
+        PersonField::State => &person.state,
+    }
+}
+
+/// Whether `email` looks like `local@domain.tld`. Deliberately loose: the
+/// only way to know an address works is to send mail to it.
+fn is_email(email: &str) -> bool {
+    let Some((local, domain)) = email.split_once('@') else {
+        return false;
+    };
+    let labels: Vec<&str> = domain.split('.').collect();
+    !local.is_empty()
+        && local.len() <= 64
+        && local
+            .chars()
+            .all(|c| c.is_ascii_graphic() && !"(),:;<>@[\\]\"".contains(c))
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            !label.is_empty()
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+        && labels
+            .last()
+            .is_some_and(|tld| tld.len() >= 2 && tld.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+fn check_phone(config: &ValidationConfig, phone: &str) -> Result<(), String> {
+    let symbols = phone.strip_prefix('+').unwrap_or(phone);
+    if !symbols
+        .chars()
+        .all(|c| c.is_ascii_digit() || " -.()".contains(c))
+    {
+        return Err("may only contain digits, spaces, - . ( ) and a leading +".to_string());
+    }
+    let digits = symbols.chars().filter(char::is_ascii_digit).count();
+    if digits < config.phone_min_digits || digits > config.phone_max_digits {
+        return Err(format!(
+            "must have {} to {} digits",
+            config.phone_min_digits, config.phone_max_digits
+        ));
+    }
+    Ok(())
+}
+
+/// Every rule in `config` that `person` breaks, at most one per field.
+pub fn validate(config: &ValidationConfig, person: &NewPerson) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    for field in PersonField::ALL {
+        let value = value(person, field);
+        let max = config.max_lengths.get(field);
+        let result = if value.is_empty() {
+            if config.required.contains(&field) {
+                Err("is required".to_string())
+            } else {
+                Ok(())
+            }
+        } else if value.chars().count() > max {
+            Err(format!("must be at most {} characters", max))
+        } else {
+            match field {
+                PersonField::Email if config.check_email && !is_email(value) => {
+                    Err("is not a valid email address".to_string())
+                }
+                PersonField::Phone => check_phone(config, value),
+                PersonField::State
+                    if !config.states.is_empty() && !config.states.iter().any(|s| s == value) =>
+                {
+                    Err("is not an accepted state code".to_string())
+                }
+                _ => Ok(()),
+            }
+        };
+        if let Err(message) = result {
+            errors.push(FieldError { field, message });
+        }
+    }
+    errors
+}
+
+/// `errors` as a JSON array of `{"field", "message"}` objects.
+pub fn errors_json(errors: &[FieldError]) -> Value {
+    errors
+        .iter()
+        .map(|error| json!({"field": error.field.as_str(), "message": error.message}))
+        .collect()
+}
+
+/// 422 listing `errors`.
+pub fn unprocessable(errors: &[FieldError]) -> Response {
+    Response::new(
+        422,
+        "application/json",
+        json!({"errors": errors_json(errors)}).to_string(),
+    )
+}
@@ -0,0 +1,160 @@
+use http_server::access_log::{AccessEntry, AccessLog};
+use http_server::config::{AccessLogConfig, AccessLogFormat};
+use http_server::request::Request;
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A log file of its own for each test, removed with its rotations.
+struct TempLog(PathBuf);
+
+impl TempLog {
+    fn new(name: &str) -> Self {
+        let log = TempLog(std::env::temp_dir().join(format!(
+            "access-{}-{}.log",
+            std::process::id(),
+            name
+        )));
+        log.clean();
+        log
+    }
+
+    fn path(&self) -> String {
+        self.0.to_str().unwrap().to_string()
+    }
+
+    fn read(&self, suffix: &str) -> String {
+        fs::read_to_string(format!("{}{}", self.path(), suffix)).unwrap_or_default()
+    }
+
+    fn clean(&self) {
+        for suffix in ["", ".1", ".2", ".3"] {
+            let _ = fs::remove_file(format!("{}{}", self.path(), suffix));
+        }
+    }
+}
+
+impl Drop for TempLog {
+    fn drop(&mut self) {
+        self.clean();
+    }
+}
+
+fn request() -> Request {
+    let mut request = Request::parse(
+        "GET /person/1?x=\"y\" HTTP/1.1\r\nReferer: https://example.com/\r\n\
+         User-Agent: curl/8.0\r\nX-Request-Id: req-1\r\n\r\n",
+    )
+    .unwrap();
+    request.id = "req-1".to_string();
+    request
+}
+
+/// 10 Oct 2000 13:55:36 UTC, the example time of the Common Log Format.
+fn entry(request: Option<&Request>) -> AccessEntry<'_> {
+    AccessEntry {
+        time: UNIX_EPOCH + Duration::from_secs(971_186_136),
+        peer_addr: Some("192.0.2.7".parse().unwrap()),
+        request,
+        status: 200,
+        bytes: 2326,
+        latency: Duration::from_micros(1500),
+    }
+}
+
+fn line(format: AccessLogFormat, name: &str, request: Option<&Request>) -> String {
+    let log = TempLog::new(name);
+    let access_log = AccessLog::new(&AccessLogConfig {
+        format,
+        path: Some(log.path()),
+        ..AccessLogConfig::default()
+    });
+    access_log.record(&entry(request));
+    log.read("")
+}
+
+#[test]
+fn common_lines_follow_the_common_log_format() {
+    let request = request();
+    assert_eq!(
+        line(AccessLogFormat::Common, "common", Some(&request)),
+        "192.0.2.7 - - [10/Oct/2000:13:55:36 +0000] \"GET /person/1?x=\\\"y\\\" HTTP/1.1\" \
+         200 2326 req-1 1.500ms\n"
+    );
+}
+
+#[test]
+fn combined_lines_add_the_referer_and_user_agent() {
+    let request = request();
+    assert_eq!(
+        line(AccessLogFormat::Combined, "combined", Some(&request)),
+        "192.0.2.7 - - [10/Oct/2000:13:55:36 +0000] \"GET /person/1?x=\\\"y\\\" HTTP/1.1\" \
+         200 2326 \"https://example.com/\" \"curl/8.0\" req-1 1.500ms\n"
+    );
+}
+
+#[test]
+fn unparsed_requests_are_logged_with_dashes() {
+    assert_eq!(
+        line(AccessLogFormat::Combined, "unparsed", None),
+        "192.0.2.7 - - [10/Oct/2000:13:55:36 +0000] \"-\" 200 2326 \"-\" \"-\" - 1.500ms\n"
+    );
+}
+
+#[test]
+fn json_lines_are_one_object_each() {
+    let request = request();
+    let line = line(AccessLogFormat::Json, "json", Some(&request));
+    let object: Value = serde_json::from_str(line.trim_end()).unwrap();
+
+    assert_eq!(object["time"], "2000-10-10T13:55:36Z");
+    assert_eq!(object["peer_addr"], "192.0.2.7");
+    assert_eq!(object["method"], "GET");
+    assert_eq!(object["path"], "/person/1?x=\"y\"");
+    assert_eq!(object["status"], 200);
+    assert_eq!(object["bytes"], 2326);
+    assert_eq!(object["latency_ms"], 1.5);
+    assert_eq!(object["request_id"], "req-1");
+    assert_eq!(object["referer"], "https://example.com/");
+    assert_eq!(object["user_agent"], "curl/8.0");
+}
+
+#[test]
+fn full_files_are_rotated_keeping_max_files() {
+    let log = TempLog::new("rotate");
+    let access_log = AccessLog::new(&AccessLogConfig {
+        path: Some(log.path()),
+        max_bytes: 100,
+        max_files: 2,
+        ..AccessLogConfig::default()
+    });
+    let line_len = line(AccessLogFormat::Common, "rotate-length", None).len();
+    assert!(line_len * 2 > 100 && line_len <= 100);
+
+    for _ in 0..4 {
+        access_log.record(&entry(None));
+    }
+
+    for suffix in ["", ".1", ".2"] {
+        assert_eq!(log.read(suffix).len(), line_len, "{}", suffix);
+    }
+    assert!(fs::metadata(format!("{}.3", log.path())).is_err());
+}
+
+#[test]
+fn disabled_logs_write_nothing() {
+    let log = TempLog::new("disabled");
+    let access_log = AccessLog::new(&AccessLogConfig {
+        enabled: false,
+        path: Some(log.path()),
+        ..AccessLogConfig::default()
+    });
+
+    access_log.record(&AccessEntry {
+        time: SystemTime::now(),
+        ..entry(None)
+    });
+
+    assert!(fs::metadata(log.path()).is_err());
+}
@@ -0,0 +1,126 @@
+use flate2::read::{GzDecoder, ZlibDecoder};
+use http_server::compression;
+use http_server::config::{CompressionConfig, Encoding};
+use http_server::fake_data::Rng;
+use http_server::request::Request;
+use http_server::response::Response;
+use std::io::Read;
+
+fn request(accept_encoding: &str) -> Request {
+    Request::parse(&format!(
+        "GET /persons/export HTTP/1.1\r\nAccept-Encoding: {}\r\n\r\n",
+        accept_encoding
+    ))
+    .unwrap()
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+fn body() -> Vec<u8> {
+    "name,email\n".repeat(500).into_bytes()
+}
+
+fn compress(accept_encoding: &str, response: Response) -> Response {
+    compression::apply(
+        &CompressionConfig::default(),
+        &request(accept_encoding),
+        response,
+    )
+}
+
+fn decode(encoding: &str, body: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::new();
+    match encoding {
+        "br" => brotli::Decompressor::new(body, 4096)
+            .read_to_end(&mut decoded)
+            .unwrap(),
+        "gzip" => GzDecoder::new(body).read_to_end(&mut decoded).unwrap(),
+        "deflate" => ZlibDecoder::new(body).read_to_end(&mut decoded).unwrap(),
+        other => panic!("Unexpected encoding {}", other),
+    };
+    decoded
+}
+
+#[test]
+fn bodies_are_encoded_as_negotiated() {
+    for (accept, expected) in [
+        ("gzip, deflate, br", "br"),
+        ("gzip;q=1, br;q=0.5", "gzip"),
+        ("deflate", "deflate"),
+    ] {
+        let response = compress(accept, Response::text(200, body()));
+
+        assert_eq!(header(&response, "Content-Encoding"), Some(expected));
+        assert_eq!(header(&response, "Vary"), Some("Accept-Encoding"));
+        assert!(response.body.len() < body().len());
+        assert_eq!(decode(expected, &response.body), body());
+    }
+}
+
+#[test]
+fn unacceptable_encodings_leave_the_body_alone() {
+    let response = compress("identity", Response::text(200, body()));
+
+    assert_eq!(header(&response, "Content-Encoding"), None);
+    assert_eq!(header(&response, "Vary"), Some("Accept-Encoding"));
+    assert_eq!(response.body, body());
+}
+
+#[test]
+fn small_skipped_and_encoded_bodies_are_left_alone() {
+    let small = compress("gzip", Response::text(200, "name,email\n"));
+    assert_eq!(header(&small, "Content-Encoding"), None);
+    assert_eq!(header(&small, "Vary"), None);
+
+    let image = compress("gzip", Response::new(200, "image/png; x=y", body()));
+    assert_eq!(header(&image, "Content-Encoding"), None);
+
+    let encoded = compress(
+        "gzip",
+        Response::text(200, body()).with_header("Content-Encoding", "identity"),
+    );
+    assert_eq!(header(&encoded, "Content-Encoding"), Some("identity"));
+    assert_eq!(encoded.body, body());
+
+    let no_content = compress("gzip", Response::text(204, body()));
+    assert_eq!(no_content.body, body());
+}
+
+#[test]
+fn incompressible_bodies_are_sent_as_they_are() {
+    let mut rng = Rng::new(1);
+    let noise: Vec<u8> = (0..200)
+        .flat_map(|_| rng.next_u64().to_le_bytes())
+        .collect();
+    let response = compress(
+        "deflate",
+        Response::new(200, "application/octet-stream", noise.clone()),
+    );
+
+    assert_eq!(header(&response, "Content-Encoding"), None);
+    assert_eq!(response.body, noise);
+}
+
+#[test]
+fn streamed_bodies_are_encoded_as_they_are_written() {
+    let response = Response::stream(200, "text/csv", |writer| writer.write_all(&body()));
+    let mut response = compression::apply(
+        &CompressionConfig {
+            encodings: vec![Encoding::Gzip],
+            ..CompressionConfig::default()
+        },
+        &request("br, gzip"),
+        response,
+    );
+
+    assert_eq!(header(&response, "Content-Encoding"), Some("gzip"));
+    let mut written = Vec::new();
+    (response.stream.take().unwrap())(&mut written).unwrap();
+    assert_eq!(decode("gzip", &written), body());
+}
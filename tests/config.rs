@@ -0,0 +1,100 @@
+use http_server::config::{Config, ConfigError};
+use std::fs;
+
+/// Loads `content` from a file of its own.
+fn load(name: &str, content: &str) -> Result<Config, ConfigError> {
+    let path = std::env::temp_dir().join(format!("config-{}-{}.toml", std::process::id(), name));
+    fs::write(&path, content).unwrap();
+    let config = Config::load(Some(path.to_str().unwrap()));
+    fs::remove_file(&path).unwrap();
+    config
+}
+
+fn report(name: &str, content: &str) -> String {
+    match load(name, content) {
+        Ok(_) => panic!("Accepted {}", content),
+        Err(e) => e.to_string(),
+    }
+}
+
+#[test]
+fn the_repository_config_is_valid() {
+    let config = Config::load(Some("config.toml")).unwrap_or_else(|e| panic!("{}", e));
+    assert_eq!(config.path, "config.toml");
+}
+
+#[test]
+fn every_issue_is_reported_at_once() {
+    let report = report(
+        "issues",
+        r#"
+[server]
+port = "eighty"
+colour = "blue"
+
+[access_log]
+format = "fancy"
+
+[nonsense]
+x = 1
+"#,
+    );
+
+    let lines: Vec<&str> = report.lines().collect();
+    assert!(
+        lines[0].starts_with("Invalid configuration in "),
+        "{}",
+        report
+    );
+    for expected in [
+        "  database.url: is required",
+        "  server.colour: unknown key, expected one of `host`, `port`",
+        "  server.port: invalid type: string \"eighty\", expected u16",
+        "  access_log.format: unknown variant `fancy`",
+        "  nonsense: unknown section",
+    ] {
+        assert!(
+            lines.iter().any(|line| line.starts_with(expected)),
+            "{} missing from\n{}",
+            expected,
+            report
+        );
+    }
+}
+
+#[test]
+fn sections_that_parse_are_still_validated() {
+    let report = report(
+        "validated",
+        r#"
+[database]
+url = "mysql://localhost/app"
+
+[server]
+port = "eighty"
+
+[http_rate_limit]
+per_second = 0
+"#,
+    );
+
+    assert!(report.contains("server.port"), "{}", report);
+    assert!(report.contains("http_rate_limit.per_second"), "{}", report);
+}
+
+#[test]
+fn syntax_errors_and_missing_files_are_reported_with_the_path() {
+    let report = report("syntax", "[server\nport = 1");
+    assert!(report.starts_with("Failed to parse "), "{}", report);
+
+    let Err(error) = Config::load(Some("/nonexistent/config.toml")) else {
+        panic!("Loaded a missing file");
+    };
+    assert!(
+        error
+            .to_string()
+            .starts_with("Failed to read /nonexistent/config.toml: "),
+        "{}",
+        error
+    );
+}
@@ -0,0 +1,169 @@
+use http_server::config::CorsConfig;
+use http_server::cors;
+use http_server::request::Request;
+use http_server::response::Response;
+
+fn config(origins: &[&str]) -> CorsConfig {
+    CorsConfig {
+        enabled: true,
+        allowed_origins: origins.iter().map(|origin| origin.to_string()).collect(),
+        ..CorsConfig::default()
+    }
+}
+
+fn request(method: &str, headers: &str) -> Request {
+    Request::parse(&format!("{} /persons HTTP/1.1\r\n{}\r\n", method, headers)).unwrap()
+}
+
+fn preflight_request(origin: &str) -> Request {
+    request(
+        "OPTIONS",
+        &format!(
+            "Origin: {}\r\nAccess-Control-Request-Method: POST\r\n\
+             Access-Control-Request-Headers: content-type, x-trace\r\n",
+            origin
+        ),
+    )
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+#[test]
+fn preflights_need_an_origin_and_a_requested_method() {
+    assert!(cors::is_preflight(&preflight_request(
+        "https://app.example"
+    )));
+    assert!(!cors::is_preflight(&request(
+        "OPTIONS",
+        "Origin: https://app.example\r\n"
+    )));
+    assert!(!cors::is_preflight(&request(
+        "OPTIONS",
+        "Access-Control-Request-Method: POST\r\n"
+    )));
+    assert!(!cors::is_preflight(&request(
+        "POST",
+        "Origin: https://app.example\r\nAccess-Control-Request-Method: POST\r\n"
+    )));
+}
+
+#[test]
+fn allowed_origins_get_the_route_methods() {
+    let config = config(&["https://app.example"]);
+    let response = cors::preflight(
+        &config,
+        &preflight_request("https://APP.example"),
+        &["GET", "POST"],
+    )
+    .unwrap();
+
+    assert_eq!(response.status, 204);
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Origin"),
+        Some("https://APP.example")
+    );
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Methods"),
+        Some("GET, POST")
+    );
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Headers"),
+        Some("Authorization, Content-Type, Idempotency-Key, X-Api-Key, X-Request-Id")
+    );
+    assert_eq!(header(&response, "Access-Control-Max-Age"), Some("600"));
+    assert_eq!(header(&response, "Vary"), Some("Origin"));
+    assert_eq!(header(&response, "Access-Control-Allow-Credentials"), None);
+}
+
+#[test]
+fn other_origins_get_no_preflight_answer() {
+    let config = config(&["https://app.example"]);
+    assert!(
+        cors::preflight(
+            &config,
+            &preflight_request("https://evil.example"),
+            &["GET"]
+        )
+        .is_none()
+    );
+}
+
+#[test]
+fn methods_and_headers_follow_the_config() {
+    let config = CorsConfig {
+        allowed_methods: vec!["get".to_string()],
+        allowed_headers: vec!["*".to_string()],
+        ..config(&["*"])
+    };
+    let response = cors::preflight(
+        &config,
+        &preflight_request("https://app.example"),
+        &["GET", "POST"],
+    )
+    .unwrap();
+
+    assert_eq!(header(&response, "Access-Control-Allow-Origin"), Some("*"));
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Methods"),
+        Some("GET")
+    );
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Headers"),
+        Some("content-type, x-trace")
+    );
+}
+
+#[test]
+fn credentials_echo_the_origin_instead_of_a_wildcard() {
+    let config = CorsConfig {
+        allow_credentials: true,
+        ..config(&["*"])
+    };
+    let response =
+        cors::preflight(&config, &preflight_request("https://app.example"), &["GET"]).unwrap();
+
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Origin"),
+        Some("https://app.example")
+    );
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Credentials"),
+        Some("true")
+    );
+}
+
+#[test]
+fn responses_to_allowed_origins_expose_headers() {
+    let config = config(&["https://app.example"]);
+    let allowed = request("GET", "Origin: https://app.example\r\n");
+    let other = request("GET", "Origin: https://evil.example\r\n");
+
+    let response = cors::apply(&config, &allowed, Response::text(200, "ok"));
+    assert_eq!(
+        header(&response, "Access-Control-Allow-Origin"),
+        Some("https://app.example")
+    );
+    assert!(
+        header(&response, "Access-Control-Expose-Headers")
+            .unwrap()
+            .contains("X-Request-Id")
+    );
+
+    let response = cors::apply(&config, &other, Response::text(200, "ok"));
+    assert_eq!(header(&response, "Access-Control-Allow-Origin"), None);
+
+    // A preflight answer already carries its headers.
+    let preflight =
+        cors::preflight(&config, &preflight_request("https://app.example"), &["GET"]).unwrap();
+    let count = preflight.headers.len();
+    assert_eq!(
+        cors::apply(&config, &allowed, preflight).headers.len(),
+        count
+    );
+}
@@ -0,0 +1,56 @@
+use http_server::data_generator::DataGenerator;
+use http_server::fake_data::Rng;
+
+#[test]
+fn rng_matches_the_splitmix64_reference() {
+    let mut rng = Rng::new(0);
+    assert_eq!(rng.next_u64(), 0xE220_A839_7B1D_CDAF);
+    assert_eq!(rng.next_u64(), 0x6E78_9E6A_A1B9_65F4);
+    assert_eq!(rng.next_u64(), 0x06C4_5D18_8009_454F);
+}
+
+#[test]
+fn row_streams_depend_only_on_the_seed_and_index() {
+    let first: Vec<u64> = (0..100).map(|i| Rng::for_row(42, i).next_u64()).collect();
+    let reversed: Vec<u64> = (0..100)
+        .rev()
+        .map(|i| Rng::for_row(42, i).next_u64())
+        .collect();
+    assert!(first.iter().eq(reversed.iter().rev()));
+
+    let mut unique = first.clone();
+    unique.sort_unstable();
+    unique.dedup();
+    assert_eq!(unique.len(), first.len());
+    assert_ne!(Rng::for_row(43, 0).next_u64(), first[0]);
+}
+
+#[test]
+fn bounded_values_stay_in_range() {
+    let mut rng = Rng::new(7);
+    for _ in 0..1000 {
+        assert!(rng.below(10) < 10);
+        assert!((5..=9).contains(&rng.range(5, 9)));
+    }
+    assert_eq!(rng.range(3, 3), 3);
+}
+
+#[test]
+fn persons_are_repeatable_from_the_seed() {
+    let generator = DataGenerator::new(42);
+    let again = DataGenerator::new(42);
+    let other = DataGenerator::new(43);
+
+    for index in [0, 1, 1000] {
+        let person = generator.person(index);
+        let repeat = again.person(index);
+        assert_eq!(
+            (&person.name, &person.email, &person.phone, &person.address),
+            (&repeat.name, &repeat.email, &repeat.phone, &repeat.address)
+        );
+        assert_eq!((&person.city, &person.state), (&repeat.city, &repeat.state));
+        assert_eq!(person.version, 1);
+    }
+    assert_ne!(generator.person(0).email, other.person(0).email);
+    assert_ne!(generator.person(0).email, generator.person(1).email);
+}
@@ -0,0 +1,161 @@
+use http_server::generation_spec::{self, GenerationSpec, SpecGenerator};
+use http_server::row_generator::RowGenerator;
+use mysql::Value;
+use std::collections::HashSet;
+use std::fs;
+
+/// Loads `content` through a spec file, as `populate.spec_file` would.
+fn load(name: &str, content: &str) -> Result<GenerationSpec, String> {
+    let path = std::env::temp_dir().join(format!(
+        "generation-spec-{}-{}.toml",
+        std::process::id(),
+        name
+    ));
+    fs::write(&path, content).unwrap();
+    let spec = GenerationSpec::load(path.to_str().unwrap());
+    fs::remove_file(&path).unwrap();
+    spec
+}
+
+fn generator(name: &str, columns: &str) -> SpecGenerator {
+    let spec = load(name, &format!("[[table]]\nname = \"t\"\n{}", columns))
+        .unwrap_or_else(|e| panic!("{}", e));
+    SpecGenerator::new(spec.table("t").unwrap(), 42)
+}
+
+#[test]
+fn unique_columns_never_repeat_up_to_their_capacity() {
+    let generator = generator(
+        "unique",
+        r#"
+[[table.columns]]
+name = "code"
+kind = "template"
+pattern = "[AB]#"
+unique = true
+"#,
+    );
+
+    assert_eq!(generator.capacity(), Some(20));
+    let codes: HashSet<String> = (0..20).map(|i| generator.row(i)[0].as_sql(false)).collect();
+    assert_eq!(codes.len(), 20);
+    assert!(generator.check_capacity(20).is_ok());
+    assert_eq!(
+        generator.check_capacity(21).unwrap_err(),
+        "Unique columns of t allow at most 20 rows"
+    );
+}
+
+#[test]
+fn the_smallest_unique_column_sets_the_capacity() {
+    let generator = generator(
+        "smallest",
+        r#"
+[[table.columns]]
+name = "id"
+kind = "sequence"
+unique = true
+
+[[table.columns]]
+name = "size"
+kind = "enum"
+values = ["S", "M", "L"]
+unique = true
+
+[[table.columns]]
+name = "score"
+kind = "range"
+min = 0
+max = 9
+unique = true
+"#,
+    );
+
+    assert_eq!(generator.capacity(), Some(3));
+}
+
+#[test]
+fn rows_are_repeatable_and_follow_their_kinds() {
+    let generator = generator(
+        "kinds",
+        r#"
+[[table.columns]]
+name = "id"
+kind = "sequence"
+start = 10
+step = 5
+
+[[table.columns]]
+name = "price"
+kind = "range"
+min = 1
+max = 2
+decimals = 2
+
+[[table.columns]]
+name = "note"
+kind = "enum"
+values = ["x"]
+null_ratio = 1.0
+"#,
+    );
+
+    for index in 0..100 {
+        let row = generator.row(index);
+        assert_eq!(row, generator.row(index));
+        assert_eq!(row[0], Value::Int(10 + 5 * index as i64));
+        match row[1] {
+            Value::Double(price) => assert!((1.0..=2.0).contains(&price), "{}", price),
+            ref other => panic!("Unexpected price {:?}", other),
+        }
+        assert_eq!(row[2], Value::NULL);
+    }
+}
+
+#[test]
+fn invalid_specs_are_refused() {
+    let cases = [
+        ("name = \"t\"\ncolumns = []", "Table t has no columns"),
+        (
+            "name = \"t-1\"\n[[table.columns]]\nname = \"a\"\nkind = \"sequence\"",
+            "Invalid table name",
+        ),
+        (
+            "name = \"t\"\n[[table.columns]]\nname = \"a\"\nkind = \"sequence\"\nstep = 0",
+            "t.a: step must not be 0",
+        ),
+        (
+            "name = \"t\"\n[[table.columns]]\nname = \"a\"\nkind = \"template\"\npattern = \"[z-a]\"",
+            "t.a: invalid range z-a",
+        ),
+        (
+            "name = \"t\"\n[[table.columns]]\nname = \"a\"\nkind = \"range\"\nmin = 2\nmax = 1",
+            "t.a: min must not exceed max",
+        ),
+        (
+            "name = \"t\"\n[[table.columns]]\nname = \"a\"\nkind = \"template\"\n\
+             pattern = \"********************\"\nunique = true",
+            "t.a: too many possible values to keep unique",
+        ),
+    ];
+    for (i, (table, expected)) in cases.into_iter().enumerate() {
+        let error = load(&format!("invalid-{}", i), &format!("[[table]]\n{}", table))
+            .err()
+            .unwrap_or_else(|| panic!("Accepted {}", table));
+        assert!(error.contains(expected), "{}", error);
+    }
+}
+
+#[test]
+fn only_person_falls_back_to_the_built_in_generator() {
+    let person = generation_spec::generator_for(None, "person", 1).unwrap();
+    assert_eq!(person.table(), "person");
+    let error = generation_spec::generator_for(None, "orders", 1)
+        .err()
+        .unwrap();
+    assert_eq!(error, "No generation spec for table orders");
+
+    let orders = generation_spec::generator_for(Some("generation.toml"), "orders", 1).unwrap();
+    assert_eq!(orders.columns()[1], "reference");
+    assert_eq!(orders.capacity(), Some(6 * 1000 * 26 * 26 * 100));
+}
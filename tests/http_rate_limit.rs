@@ -0,0 +1,67 @@
+use http_server::auth::{self, Authenticator};
+use http_server::config::{ApiKeyConfig, AuthConfig, ClientKey, HttpRateLimitConfig, Role};
+use http_server::http_rate_limit::{HttpRateLimiter, RateLimitDecision};
+use http_server::request::Request;
+
+fn limiter(key: ClientKey) -> HttpRateLimiter {
+    HttpRateLimiter::new(&HttpRateLimitConfig {
+        key,
+        per_second: 1,
+        burst: Some(1),
+        ..HttpRateLimitConfig::default()
+    })
+}
+
+fn authenticator() -> Authenticator {
+    Authenticator::new(&AuthConfig {
+        enabled: true,
+        keys: vec![ApiKeyConfig {
+            name: "etl".to_string(),
+            sha256: auth::hash_key("s3cret"),
+            role: Role::Writer,
+        }],
+        ..AuthConfig::default()
+    })
+    .unwrap()
+}
+
+fn get(addr: &str, api_key: Option<&str>) -> Request {
+    let header = api_key.map_or(String::new(), |key| format!("X-Api-Key: {}\r\n", key));
+    let mut request = Request::parse(&format!("GET /persons HTTP/1.1\r\n{}\r\n", header)).unwrap();
+    request.peer_addr = Some(addr.parse().unwrap());
+    request
+}
+
+/// Whether a `GET` from `addr` carrying `api_key` is over its quota.
+fn limited(limiter: &HttpRateLimiter, addr: &str, api_key: Option<&str>) -> bool {
+    let decision = limiter.check(&get(addr, api_key), &authenticator());
+    matches!(decision, RateLimitDecision::Limited { .. })
+}
+
+#[test]
+fn made_up_api_keys_share_the_ip_quota() {
+    let limiter = limiter(ClientKey::ApiKey);
+
+    assert!(!limited(&limiter, "192.0.2.1", Some("guess-1")));
+    assert!(limited(&limiter, "192.0.2.1", Some("guess-2")));
+    assert!(limited(&limiter, "192.0.2.1", None));
+}
+
+#[test]
+fn verified_api_keys_get_a_quota_of_their_own() {
+    let limiter = limiter(ClientKey::ApiKey);
+
+    assert!(!limited(&limiter, "192.0.2.1", None));
+    assert!(!limited(&limiter, "192.0.2.1", Some("s3cret")));
+    // The key's quota follows it to another address.
+    assert!(limited(&limiter, "192.0.2.2", Some("s3cret")));
+}
+
+#[test]
+fn keys_are_ignored_when_limiting_by_ip() {
+    let limiter = limiter(ClientKey::Ip);
+
+    assert!(!limited(&limiter, "192.0.2.1", Some("s3cret")));
+    assert!(limited(&limiter, "192.0.2.1", Some("s3cret")));
+    assert!(!limited(&limiter, "192.0.2.2", Some("s3cret")));
+}
@@ -0,0 +1,194 @@
+use http_server::auth::Principal;
+use http_server::config::{IdempotencyConfig, Role};
+use http_server::idempotency::IdempotencyStore;
+use http_server::request::Request;
+use http_server::response::Response;
+use serde_json::Map;
+use std::cell::Cell;
+use std::net::IpAddr;
+
+/// A keyed `POST` from 192.0.2.1.
+fn post(key: &str, body: &str) -> Request {
+    let mut request = Request::parse(&format!(
+        "POST /persons HTTP/1.1\r\nIdempotency-Key: {}\r\n\r\n",
+        key
+    ))
+    .unwrap();
+    request.body = body.to_string();
+    from(request, "192.0.2.1")
+}
+
+fn from(mut request: Request, addr: &str) -> Request {
+    request.peer_addr = Some(addr.parse::<IpAddr>().unwrap());
+    request
+}
+
+fn as_principal(mut request: Request, name: &str) -> Request {
+    request.principal = Some(Principal {
+        name: name.to_string(),
+        role: Role::Writer,
+        claims: None,
+    });
+    request
+}
+
+/// As the caller of a JWT whose `sub` is `name`.
+fn as_token(mut request: Request, name: &str) -> Request {
+    request.principal = Some(Principal {
+        name: name.to_string(),
+        role: Role::Writer,
+        claims: Some(Map::new()),
+    });
+    request
+}
+
+fn header<'a>(response: &'a Response, name: &str) -> Option<&'a str> {
+    response
+        .headers
+        .iter()
+        .find(|(header, _)| header.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Handles `request`, answering `status` and counting the handler's runs.
+fn send(store: &IdempotencyStore, request: &Request, runs: &Cell<u32>, status: u16) -> Response {
+    store.handle(&IdempotencyConfig::default(), request, || {
+        runs.set(runs.get() + 1);
+        Response::text(status, format!("run {}", runs.get()))
+    })
+}
+
+#[test]
+fn a_repeated_request_gets_the_first_response() {
+    let store = IdempotencyStore::new();
+    let runs = Cell::new(0);
+
+    let first = send(&store, &post("k1", "name=Ada"), &runs, 201);
+    let second = send(&store, &post("k1", "name=Ada"), &runs, 201);
+
+    assert_eq!(runs.get(), 1);
+    assert_eq!(second.status, 201);
+    assert_eq!(second.body, first.body);
+    assert_eq!(header(&first, "Idempotent-Replayed"), None);
+    assert_eq!(header(&second, "Idempotent-Replayed"), Some("true"));
+}
+
+#[test]
+fn a_key_reused_for_another_body_is_a_conflict() {
+    let store = IdempotencyStore::new();
+    let runs = Cell::new(0);
+
+    send(&store, &post("k1", "name=Ada"), &runs, 201);
+    let response = send(&store, &post("k1", "name=Grace"), &runs, 201);
+
+    assert_eq!(runs.get(), 1);
+    assert_eq!(response.status, 409);
+}
+
+#[test]
+fn a_repeat_of_a_request_still_running_is_a_conflict() {
+    let store = IdempotencyStore::new();
+    let config = IdempotencyConfig::default();
+    let request = post("k1", "name=Ada");
+
+    let mut nested = None;
+    let first = store.handle(&config, &request, || {
+        nested = Some(store.handle(&config, &request, || {
+            panic!("The repeat must not run while the first is in flight")
+        }));
+        Response::text(201, "created")
+    });
+
+    let nested = nested.unwrap();
+    assert_eq!(nested.status, 409);
+    assert_eq!(header(&nested, "Retry-After"), Some("1"));
+    assert_eq!(first.status, 201);
+}
+
+#[test]
+fn server_errors_are_not_replayed() {
+    let store = IdempotencyStore::new();
+    let runs = Cell::new(0);
+
+    assert_eq!(
+        send(&store, &post("k1", "name=Ada"), &runs, 503).status,
+        503
+    );
+    assert_eq!(
+        send(&store, &post("k1", "name=Ada"), &runs, 201).status,
+        201
+    );
+
+    assert_eq!(runs.get(), 2);
+}
+
+#[test]
+fn keys_are_scoped_to_the_caller() {
+    let store = IdempotencyStore::new();
+    let runs = Cell::new(0);
+
+    send(&store, &as_principal(post("k1", "x"), "ops"), &runs, 201);
+    send(&store, &as_principal(post("k1", "x"), "etl"), &runs, 201);
+    assert_eq!(runs.get(), 2);
+
+    // Without credentials the client address is the caller.
+    send(&store, &from(post("k2", "x"), "192.0.2.1"), &runs, 201);
+    send(&store, &from(post("k2", "x"), "192.0.2.2"), &runs, 201);
+    assert_eq!(runs.get(), 4);
+    let replayed = send(&store, &from(post("k2", "x"), "192.0.2.1"), &runs, 201);
+    assert_eq!(runs.get(), 4);
+    assert_eq!(header(&replayed, "Idempotent-Replayed"), Some("true"));
+}
+
+#[test]
+fn api_keys_and_tokens_with_the_same_name_are_different_callers() {
+    let store = IdempotencyStore::new();
+    let runs = Cell::new(0);
+
+    send(&store, &as_principal(post("k1", "x"), "ops"), &runs, 201);
+    let response = send(&store, &as_token(post("k1", "x"), "ops"), &runs, 201);
+
+    assert_eq!(runs.get(), 2);
+    assert_eq!(header(&response, "Idempotent-Replayed"), None);
+}
+
+#[test]
+fn keys_without_a_caller_are_refused() {
+    let store = IdempotencyStore::new();
+    let runs = Cell::new(0);
+    let mut request = post("k1", "x");
+    request.peer_addr = None;
+
+    assert_eq!(send(&store, &request, &runs, 201).status, 400);
+    assert_eq!(runs.get(), 0);
+    // An authenticated caller needs no address.
+    let request = as_principal(request, "ops");
+    assert_eq!(send(&store, &request, &runs, 201).status, 201);
+    assert_eq!(runs.get(), 1);
+}
+
+#[test]
+fn only_keyed_posts_are_tracked() {
+    let store = IdempotencyStore::new();
+    let runs = Cell::new(0);
+    let get = Request::parse("GET /persons HTTP/1.1\r\nIdempotency-Key: k1\r\n\r\n").unwrap();
+    let unkeyed = Request::parse("POST /persons HTTP/1.1\r\n\r\n").unwrap();
+
+    for request in [&get, &get, &unkeyed, &unkeyed] {
+        send(&store, request, &runs, 200);
+    }
+
+    assert_eq!(runs.get(), 4);
+}
+
+#[test]
+fn malformed_keys_are_refused() {
+    let store = IdempotencyStore::new();
+    let runs = Cell::new(0);
+    let too_long = "k".repeat(256);
+
+    for key in [too_long.as_str(), "has space"] {
+        assert_eq!(send(&store, &post(key, "x"), &runs, 201).status, 400);
+    }
+    assert_eq!(runs.get(), 0);
+}
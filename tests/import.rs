@@ -0,0 +1,91 @@
+use http_server::config::PersonField;
+use http_server::import::{self, Records};
+use http_server::sink::OutputFormat;
+
+fn collect(records: Records) -> Vec<(u64, Result<Vec<String>, String>)> {
+    records.collect()
+}
+
+fn values(line: &[&str]) -> Result<Vec<String>, String> {
+    Ok(line.iter().map(|value| value.to_string()).collect())
+}
+
+const REQUIRED: &[PersonField] = &[PersonField::Name, PersonField::Email];
+
+#[test]
+fn csv_columns_are_matched_by_header_in_any_order() {
+    let body = "email,id,name\nada@example.com,7,Ada\n";
+
+    let records = collect(import::parse(body, OutputFormat::Csv, REQUIRED).unwrap());
+
+    assert_eq!(
+        records,
+        [(2, values(&["Ada", "ada@example.com", "", "", "", ""]))]
+    );
+}
+
+#[test]
+fn csv_headers_need_the_required_columns() {
+    let body = "name,phone\nAda,555-0100\n";
+
+    let error = import::parse(body, OutputFormat::Csv, REQUIRED)
+        .err()
+        .unwrap();
+    assert_eq!(error, "CSV header has no email column");
+
+    let required = [PersonField::Name, PersonField::Phone];
+    assert!(import::parse(body, OutputFormat::Csv, &required).is_ok());
+    let error = import::parse("email\nx\n", OutputFormat::Csv, &required)
+        .err()
+        .unwrap();
+    assert_eq!(error, "CSV header has no name column");
+}
+
+#[test]
+fn csv_lines_with_the_wrong_field_count_are_reported_by_line() {
+    let body = "name,email\nAda,ada@example.com\nGrace\nAlan,alan@example.com\n";
+
+    let records = collect(import::parse(body, OutputFormat::Csv, REQUIRED).unwrap());
+
+    assert_eq!(records.len(), 3);
+    assert_eq!(
+        records[1],
+        (3, Err("Expected 2 fields, found 1".to_string()))
+    );
+    assert_eq!(records[2].0, 4);
+}
+
+#[test]
+fn ndjson_lines_are_read_as_objects() {
+    let body = concat!(
+        "{\"name\": \"Ada\", \"email\": \"ada@example.com\", \"state\": null}\n",
+        "\n",
+        "{\"name\": 7}\n",
+        "not json\n",
+    );
+
+    let records = collect(import::parse(body, OutputFormat::Ndjson, REQUIRED).unwrap());
+
+    assert_eq!(
+        records[0],
+        (1, values(&["Ada", "ada@example.com", "", "", "", ""]))
+    );
+    // Blank lines are skipped but still counted.
+    assert_eq!(records[1], (3, Err("name must be a string".to_string())));
+    assert_eq!(records[2].0, 4);
+    assert!(
+        records[2]
+            .1
+            .as_ref()
+            .unwrap_err()
+            .starts_with("Invalid JSON object")
+    );
+}
+
+#[test]
+fn sql_dumps_are_refused() {
+    let format = OutputFormat::Sql {
+        rows_per_statement: 1,
+    };
+    assert!(import::parse("", format, REQUIRED).is_err());
+}
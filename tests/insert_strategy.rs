@@ -0,0 +1,86 @@
+use http_server::insert_strategy::InsertStrategy;
+use std::collections::HashMap;
+
+fn from_params(pairs: &[(&str, &str)]) -> Result<InsertStrategy, String> {
+    let params: HashMap<String, String> = pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    InsertStrategy::from_params(&params)
+}
+
+#[test]
+fn parses_each_strategy() {
+    assert!(matches!(
+        InsertStrategy::parse(None, None),
+        Ok(InsertStrategy::Prepared)
+    ));
+    assert!(matches!(
+        InsertStrategy::parse(Some("prepared"), None),
+        Ok(InsertStrategy::Prepared)
+    ));
+    assert!(matches!(
+        InsertStrategy::parse(Some("multi_row"), None),
+        Ok(InsertStrategy::MultiRow {
+            rows_per_statement: 1000
+        })
+    ));
+    assert!(matches!(
+        InsertStrategy::parse(Some("multi_row"), Some(250)),
+        Ok(InsertStrategy::MultiRow {
+            rows_per_statement: 250
+        })
+    ));
+    assert!(matches!(
+        InsertStrategy::parse(Some("load_data"), None),
+        Ok(InsertStrategy::LoadData)
+    ));
+}
+
+#[test]
+fn rejects_unknown_strategies_and_bad_row_counts() {
+    let error = InsertStrategy::parse(Some("bulk"), None).unwrap_err();
+    assert_eq!(error, "Unknown insert strategy: bulk");
+    for rows in [0, 65536] {
+        let error = InsertStrategy::parse(Some("multi_row"), Some(rows)).unwrap_err();
+        assert_eq!(error, "rows_per_statement must be between 1 and 65535");
+    }
+}
+
+#[test]
+fn reads_query_parameters() {
+    assert!(matches!(
+        from_params(&[("strategy", "multi_row"), ("rows_per_statement", "10")]),
+        Ok(InsertStrategy::MultiRow {
+            rows_per_statement: 10
+        })
+    ));
+    assert_eq!(
+        from_params(&[("strategy", "multi_row"), ("rows_per_statement", "ten")]).unwrap_err(),
+        "Invalid rows_per_statement parameter"
+    );
+}
+
+#[test]
+fn wide_tables_are_capped_by_the_placeholder_limit() {
+    let strategy = InsertStrategy::parse(Some("multi_row"), Some(65535)).unwrap();
+
+    assert_eq!(
+        strategy.for_columns(7).to_string(),
+        "multi_row (9362 rows per statement)"
+    );
+    assert_eq!(
+        strategy.for_columns(1).to_string(),
+        "multi_row (65535 rows per statement)"
+    );
+    // Under the cap the configured value stands.
+    let strategy = InsertStrategy::parse(Some("multi_row"), Some(1000)).unwrap();
+    assert_eq!(
+        strategy.for_columns(7).to_string(),
+        "multi_row (1000 rows per statement)"
+    );
+    assert_eq!(
+        InsertStrategy::Prepared.for_columns(70_000).to_string(),
+        "prepared"
+    );
+}
@@ -0,0 +1,155 @@
+use http_server::config::Config;
+use http_server::model::person::{NewPerson, Person};
+use http_server::request::Request;
+use http_server::server::Server;
+use http_server::store::{PersonStore, Upsert, UpsertOutcome};
+use serde_json::Value;
+use std::sync::{Arc, Mutex};
+
+/// Keeps persons in memory, ids counting from 1.
+#[derive(Default)]
+struct MemoryStore {
+    persons: Mutex<Vec<Person>>,
+}
+
+fn stored(id: u32, person: &NewPerson, version: u32) -> Person {
+    Person {
+        id,
+        name: person.name.clone(),
+        email: person.email.clone(),
+        phone: person.phone.clone(),
+        address: person.address.clone(),
+        city: person.city.clone(),
+        state: person.state.clone(),
+        version,
+    }
+}
+
+impl PersonStore for MemoryStore {
+    fn get(&self, id: u32) -> Result<Option<Person>, String> {
+        let persons = self.persons.lock().unwrap();
+        Ok(persons.iter().find(|person| person.id == id).cloned())
+    }
+
+    fn create(&self, person: &NewPerson, _actor: Option<&str>) -> Result<u32, String> {
+        let mut persons = self.persons.lock().unwrap();
+        let id = persons.len() as u32 + 1;
+        persons.push(stored(id, person, 1));
+        Ok(id)
+    }
+
+    fn update(&self, id: u32, person: &NewPerson, _actor: Option<&str>) -> Result<bool, String> {
+        let mut persons = self.persons.lock().unwrap();
+        let Some(existing) = persons.iter_mut().find(|existing| existing.id == id) else {
+            return Ok(false);
+        };
+        *existing = stored(id, person, existing.version + 1);
+        Ok(true)
+    }
+
+    fn get_many(&self, ids: &[u32]) -> Result<Vec<Person>, String> {
+        let persons = self.persons.lock().unwrap();
+        Ok(persons
+            .iter()
+            .filter(|person| ids.contains(&person.id))
+            .cloned()
+            .collect())
+    }
+
+    fn upsert_many(
+        &self,
+        _upserts: &[Upsert],
+        _actor: Option<&str>,
+    ) -> Result<Vec<UpsertOutcome>, String> {
+        Err("Not supported by MemoryStore".to_string())
+    }
+}
+
+fn server() -> Server {
+    let config = Config::load(Some("config.toml")).unwrap_or_else(|e| panic!("{}", e));
+    Server::builder(Arc::new(config))
+        .store(MemoryStore::default())
+        .build()
+        .unwrap()
+}
+
+fn send(server: &Server, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut request = Request::parse(&format!("{} {} HTTP/1.1\r\n\r\n", method, path)).unwrap();
+    request.body = body.to_string();
+    let response = server.handle_request(&request);
+    (response.status, String::from_utf8(response.body).unwrap())
+}
+
+/// Person `id` as `persons:batchGet` reports it, every field included.
+fn get(server: &Server, id: u32) -> Value {
+    let (status, body) = send(
+        server,
+        "POST",
+        "/persons:batchGet",
+        &format!("{{\"ids\": [{}]}}", id),
+    );
+    assert_eq!(status, 200, "{}", body);
+    let results: Value = serde_json::from_str(&body).unwrap();
+    results["results"][0]["person"].clone()
+}
+
+const ADA: &str = "name=Ada+Lovelace&email=ada%40example.com&phone=555-0100\
+    &address=12+St+James+Sq&city=London&state=NY";
+
+#[test]
+fn post_person_takes_the_person_columns() {
+    let server = server();
+
+    let (status, body) = send(&server, "POST", "/person", ADA);
+    assert_eq!((status, body.as_str()), (201, "Person created with ID: 1"));
+
+    let person = get(&server, 1);
+    assert_eq!(person["name"], "Ada Lovelace");
+    assert_eq!(person["email"], "ada@example.com");
+    assert_eq!(person["phone"], "555-0100");
+    assert_eq!(person["address"], "12 St James Sq");
+    assert_eq!(person["city"], "London");
+    assert_eq!(person["state"], "NY");
+    assert_eq!(person["version"], 1);
+    assert!(person.get("age").is_none());
+}
+
+#[test]
+fn put_person_replaces_every_field() {
+    let server = server();
+    send(&server, "POST", "/person", ADA);
+
+    let (status, _) = send(
+        &server,
+        "PUT",
+        "/person/1",
+        "name=Ada+King&email=ada%40example.org",
+    );
+    assert_eq!(status, 200);
+
+    let person = get(&server, 1);
+    assert_eq!(person["name"], "Ada King");
+    assert_eq!(person["email"], "ada@example.org");
+    assert_eq!(person["phone"], "");
+    assert_eq!(person["state"], "");
+    assert_eq!(person["version"], 2);
+}
+
+#[test]
+fn age_is_no_longer_a_field() {
+    let server = server();
+
+    // The old form: only `age` besides the name.
+    let (status, body) = send(&server, "POST", "/person", "name=Ada&age=36");
+    assert_eq!(status, 422, "{}", body);
+    assert!(body.contains("email"), "{}", body);
+
+    let (status, _) = send(
+        &server,
+        "POST",
+        "/person",
+        "name=Ada&email=ada%40example.com&age=36",
+    );
+    assert_eq!(status, 201);
+    assert!(get(&server, 1).get("age").is_none());
+}
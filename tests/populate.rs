@@ -0,0 +1,74 @@
+use http_server::config::Config;
+use http_server::data_generator::DataGenerator;
+use http_server::data_inserter::CommitGate;
+use http_server::insert_strategy::InsertStrategy;
+use http_server::populate::{self, PopulateMode};
+use mysql::{OptsBuilder, Pool, PoolConstraints, PoolOpts};
+use std::sync::Arc;
+use std::thread;
+
+/// Runs one inserter thread per entry of `outcomes`, each joining the same
+/// gate and then failing, panicking or arriving as told, and returns whether
+/// each of the ones that arrived was allowed to commit.
+fn run(outcomes: &[&'static str]) -> Vec<bool> {
+    let gate = Arc::new(CommitGate::new(outcomes.len()));
+    let handles: Vec<_> = outcomes
+        .iter()
+        .map(|&outcome| {
+            let gate = Arc::clone(&gate);
+            thread::spawn(move || {
+                let arrival = gate.join();
+                match outcome {
+                    "fail" => None,
+                    "panic" => panic!("Inserter panicked"),
+                    _ => Some(arrival.may_commit()),
+                }
+            })
+        })
+        .collect();
+    handles
+        .into_iter()
+        .filter_map(|handle| handle.join().ok().flatten())
+        .collect()
+}
+
+#[test]
+fn inserters_commit_when_none_failed() {
+    assert_eq!(run(&["ok", "ok", "ok"]), [true, true, true]);
+}
+
+#[test]
+fn a_failed_inserter_makes_the_others_roll_back() {
+    assert_eq!(run(&["ok", "fail", "ok"]), [false, false]);
+}
+
+#[test]
+fn a_panicked_inserter_makes_the_others_roll_back() {
+    assert_eq!(run(&["panic", "ok"]), [false]);
+}
+
+#[test]
+fn populate_fails_when_the_inserters_cannot_connect() {
+    // Nothing listens on port 1; with no minimum the pool opens no
+    // connection until an inserter asks for one.
+    let opts = OptsBuilder::new()
+        .ip_or_hostname(Some("127.0.0.1"))
+        .tcp_port(1)
+        .pool_opts(PoolOpts::default().with_constraints(PoolConstraints::new(0, 2).unwrap()));
+    let pool = Pool::new(opts).unwrap();
+    let config = Arc::new(Config::load(Some("config.toml")).unwrap_or_else(|e| panic!("{}", e)));
+
+    for mode in [PopulateMode::Threads, PopulateMode::Tokio] {
+        let result = populate::populate(
+            pool.clone(),
+            Arc::clone(&config),
+            Arc::new(DataGenerator::new(1)),
+            100,
+            mode,
+            InsertStrategy::Prepared,
+        );
+
+        let error = result.unwrap_err();
+        assert!(error.contains("database connection"), "{}", error);
+    }
+}
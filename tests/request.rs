@@ -0,0 +1,218 @@
+use flate2::Compression;
+use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+use http_server::config::ServerConfig;
+use http_server::request::{ReadError, Request};
+use http_server::response::Response;
+use std::io::Write;
+
+fn config() -> ServerConfig {
+    ServerConfig {
+        max_body_bytes: 64 * 1024,
+        max_decoded_body_bytes: 256 * 1024,
+        max_import_bytes: 512 * 1024,
+        ..ServerConfig::default()
+    }
+}
+
+fn post(headers: &str, body: &[u8]) -> Vec<u8> {
+    post_to("/persons", headers, body)
+}
+
+fn post_to(path: &str, headers: &str, body: &[u8]) -> Vec<u8> {
+    let mut raw = format!(
+        "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n{}\r\n",
+        path,
+        body.len(),
+        headers
+    )
+    .into_bytes();
+    raw.extend_from_slice(body);
+    raw
+}
+
+fn read(raw: &[u8]) -> Result<Request, ReadError> {
+    Request::read(&mut &raw[..], &config())
+}
+
+fn rejected(raw: &[u8]) -> Response {
+    match read(raw) {
+        Ok(request) => panic!("Accepted {} {}", request.method, request.path),
+        Err(ReadError::Io(e)) => panic!("Failed to read: {}", e),
+        Err(ReadError::Rejected(response)) => response,
+    }
+}
+
+fn accepted(raw: &[u8]) -> Request {
+    match read(raw) {
+        Ok(request) => request,
+        Err(ReadError::Io(e)) => panic!("Failed to read: {}", e),
+        Err(ReadError::Rejected(response)) => panic!("Rejected with {}", response.status),
+    }
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn raw_deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+const FORM: &[u8] = b"name=Ada+Lovelace&email=ada%40example.com";
+
+#[test]
+fn reads_headers_and_body() {
+    let request = accepted(&post("X-Custom: yes\r\n", FORM));
+    assert_eq!(request.method, "POST");
+    assert_eq!(request.path, "/persons");
+    assert_eq!(request.header("x-custom"), Some("yes"));
+    assert_eq!(request.body.as_bytes(), FORM);
+    assert_eq!(request.parse_body()["email"], "ada@example.com");
+}
+
+#[test]
+fn decodes_gzip_bodies() {
+    let raw = post("Content-Encoding: gzip\r\n", &gzip(FORM));
+    let request = accepted(&raw);
+    assert_eq!(request.body.as_bytes(), FORM);
+    // Handlers never see an encoding they would have to undo again.
+    assert_eq!(request.header("content-encoding"), None);
+}
+
+#[test]
+fn decodes_zlib_and_raw_deflate_bodies() {
+    assert_eq!(
+        accepted(&post("Content-Encoding: deflate\r\n", &zlib(FORM)))
+            .body
+            .as_bytes(),
+        FORM
+    );
+    assert_eq!(
+        accepted(&post("Content-Encoding: deflate\r\n", &raw_deflate(FORM)))
+            .body
+            .as_bytes(),
+        FORM
+    );
+}
+
+#[test]
+fn undoes_stacked_encodings_last_first() {
+    let raw = post("Content-Encoding: deflate, gzip\r\n", &gzip(&zlib(FORM)));
+    assert_eq!(accepted(&raw).body.as_bytes(), FORM);
+}
+
+#[test]
+fn refuses_unknown_encodings_with_415() {
+    let response = rejected(&post("Content-Encoding: br\r\n", FORM));
+    assert_eq!(response.status, 415);
+    assert!(
+        response
+            .headers
+            .iter()
+            .any(|(name, value)| name == "Accept-Encoding" && value == "gzip, deflate")
+    );
+}
+
+#[test]
+fn refuses_bodies_that_decompress_past_the_limit() {
+    let bomb = gzip(&vec![0; config().max_decoded_body_bytes + 1]);
+    assert!(bomb.len() <= config().max_body_bytes);
+    let response = rejected(&post("Content-Encoding: gzip\r\n", &bomb));
+    assert_eq!(response.status, 413);
+}
+
+#[test]
+fn refuses_bodies_longer_than_max_body_bytes() {
+    let response = rejected(&post("", &vec![b'a'; config().max_body_bytes + 1]));
+    assert_eq!(response.status, 413);
+}
+
+#[test]
+fn imports_have_a_limit_of_their_own() {
+    let upload = vec![b'a'; config().max_decoded_body_bytes + 1];
+    assert_eq!(
+        accepted(&post_to("/persons/import?format=csv", "", &upload))
+            .body
+            .len(),
+        upload.len()
+    );
+    let bomb = gzip(&vec![0; config().max_import_bytes + 1]);
+    let response = rejected(&post_to(
+        "/persons/import",
+        "Content-Encoding: gzip\r\n",
+        &bomb,
+    ));
+    assert_eq!(response.status, 413);
+    let response = rejected(&post_to(
+        "/persons/import",
+        "",
+        &vec![b'a'; config().max_import_bytes + 1],
+    ));
+    assert_eq!(response.status, 413);
+}
+
+#[test]
+fn refuses_malformed_compressed_bodies() {
+    let response = rejected(&post("Content-Encoding: gzip\r\n", FORM));
+    assert_eq!(response.status, 400);
+}
+
+#[test]
+fn refuses_chunked_bodies_with_411() {
+    let raw =
+        b"POST /persons HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+    assert_eq!(rejected(raw).status, 411);
+}
+
+#[test]
+fn refuses_oversized_heads_with_431() {
+    let raw = format!(
+        "GET / HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+        "a".repeat(20 * 1024)
+    );
+    assert_eq!(rejected(raw.as_bytes()).status, 431);
+}
+
+#[test]
+fn refuses_malformed_requests_with_400() {
+    assert_eq!(rejected(b"\r\n\r\n").status, 400);
+    assert_eq!(
+        rejected(b"POST /persons HTTP/1.1\r\nContent-Length: ten\r\n\r\n").status,
+        400
+    );
+    assert_eq!(
+        rejected(b"POST /persons HTTP/1.1\r\nContent-Length: 10\r\n\r\nshort").status,
+        400
+    );
+}
+
+#[test]
+fn keeps_printable_client_request_ids() {
+    let request = accepted(&post("X-Request-Id: abc-123\r\n", FORM));
+    assert_eq!(request.id, "abc-123");
+}
+
+#[test]
+fn numbers_requests_without_a_usable_id() {
+    let first = accepted(&post("", FORM)).id;
+    let second = accepted(&post(
+        &format!("X-Request-Id: {}\r\n", "a".repeat(129)),
+        FORM,
+    ))
+    .id;
+
+    assert_ne!(first, second);
+    let (prefix, _) = first.split_once('-').unwrap();
+    assert_eq!(prefix.len(), 16);
+    assert!(second.starts_with(prefix), "{} {}", first, second);
+}
@@ -0,0 +1,77 @@
+use http_server::migrate;
+use http_server::model::person::NewPerson;
+use http_server::store::{MySqlPersonStore, PersonStore, Upsert, UpsertOutcome};
+use mysql::Pool;
+
+fn person(name: &str, email: &str) -> NewPerson {
+    NewPerson {
+        name: name.to_string(),
+        email: email.to_string(),
+        phone: String::new(),
+        address: String::new(),
+        city: String::new(),
+        state: String::new(),
+    }
+}
+
+/// Needs a MySQL 8.0.19 or later database it may write to, named by
+/// `TEST_DATABASE_URL`: `cargo test -- --ignored`.
+#[test]
+#[ignore]
+fn upsert_many_updates_existing_persons_and_creates_the_rest() {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL is not set");
+    let pool = Pool::new(url.as_str()).unwrap();
+    migrate::migrate(&pool).unwrap();
+    let store = MySqlPersonStore::new(pool);
+    // Unique per run so that earlier runs' rows do not match.
+    let tag = std::process::id();
+    let by_id = format!("by-id-{}@example.com", tag);
+    let by_email = format!("by-email-{}@example.com", tag);
+    let new = format!("new-{}@example.com", tag);
+    let first = store.create(&person("Ada", &by_id), None).unwrap();
+    let second = store.create(&person("Grace", &by_email), None).unwrap();
+
+    let outcomes = store
+        .upsert_many(
+            &[
+                Upsert {
+                    id: Some(first),
+                    person: person("Ada King", &by_id),
+                },
+                Upsert {
+                    id: None,
+                    person: person("Grace Hopper", &by_email.to_uppercase()),
+                },
+                Upsert {
+                    id: None,
+                    person: person("Katherine", &new),
+                },
+                Upsert {
+                    id: Some(u32::MAX),
+                    person: person("Nobody", "nobody@example.com"),
+                },
+            ],
+            Some("etl"),
+        )
+        .unwrap();
+
+    let [
+        UpsertOutcome::Updated(updated),
+        UpsertOutcome::Updated(matched),
+        UpsertOutcome::Created(created),
+        UpsertOutcome::NotFound,
+    ] = outcomes[..]
+    else {
+        panic!("Unexpected outcomes {:?}", outcomes);
+    };
+    assert_eq!((updated, matched), (first, second));
+    let ada = store.get(first).unwrap().unwrap();
+    assert_eq!((ada.name.as_str(), ada.version), ("Ada King", 2));
+    let grace = store.get(second).unwrap().unwrap();
+    assert_eq!((grace.name.as_str(), grace.version), ("Grace Hopper", 2));
+    let katherine = store.get(created).unwrap().unwrap();
+    assert_eq!(
+        (katherine.email.as_str(), katherine.version),
+        (new.as_str(), 1)
+    );
+}
@@ -0,0 +1,209 @@
+use http_server::config::{MaxLengths, PersonField, ValidationConfig};
+use http_server::model::person::NewPerson;
+use http_server::validation;
+use serde_json::Value;
+
+fn person() -> NewPerson {
+    NewPerson {
+        name: "Ada Lovelace".to_string(),
+        email: "ada@example.com".to_string(),
+        phone: "+1 (555) 010-0199".to_string(),
+        address: "12 St James's Square".to_string(),
+        city: "London".to_string(),
+        state: "NY".to_string(),
+    }
+}
+
+/// The fields `person` breaks a rule of, with their messages.
+fn errors(config: &ValidationConfig, person: &NewPerson) -> Vec<(PersonField, String)> {
+    validation::validate(config, person)
+        .into_iter()
+        .map(|error| (error.field, error.message))
+        .collect()
+}
+
+fn with(change: impl FnOnce(&mut NewPerson)) -> NewPerson {
+    let mut person = person();
+    change(&mut person);
+    person
+}
+
+#[test]
+fn a_complete_person_is_valid() {
+    assert!(errors(&ValidationConfig::default(), &person()).is_empty());
+}
+
+#[test]
+fn required_fields_may_not_be_empty() {
+    let config = ValidationConfig {
+        required: vec![PersonField::Name, PersonField::City],
+        ..ValidationConfig::default()
+    };
+    let person = with(|person| {
+        person.name.clear();
+        person.email.clear();
+        person.city.clear();
+    });
+
+    assert_eq!(
+        errors(&config, &person),
+        [
+            (PersonField::Name, "is required".to_string()),
+            (PersonField::City, "is required".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn lengths_are_counted_in_characters() {
+    let config = ValidationConfig {
+        max_lengths: MaxLengths {
+            name: 4,
+            ..MaxLengths::default()
+        },
+        ..ValidationConfig::default()
+    };
+
+    assert!(errors(&config, &with(|person| person.name = "Zoë".to_string())).is_empty());
+    assert_eq!(
+        errors(&config, &with(|person| person.name = "Zoë A".to_string())),
+        [(
+            PersonField::Name,
+            "must be at most 4 characters".to_string()
+        )]
+    );
+}
+
+#[test]
+fn emails_need_a_local_part_and_a_dotted_domain() {
+    let config = ValidationConfig::default();
+    for email in ["a.b+tag@mail.example.co", "x@a-b.io"] {
+        let person = with(|person| person.email = email.to_string());
+        assert!(errors(&config, &person).is_empty(), "{}", email);
+    }
+    for email in [
+        "ada",
+        "@example.com",
+        "ada@localhost",
+        "ada@example.c0m",
+        "ada@-example.com",
+        "ada@example..com",
+        "a da@example.com",
+    ] {
+        let person = with(|person| person.email = email.to_string());
+        assert_eq!(
+            errors(&config, &person),
+            [(
+                PersonField::Email,
+                "is not a valid email address".to_string()
+            )],
+            "{}",
+            email
+        );
+    }
+
+    let unchecked = ValidationConfig {
+        check_email: false,
+        ..ValidationConfig::default()
+    };
+    assert!(errors(&unchecked, &with(|person| person.email = "ada".to_string())).is_empty());
+}
+
+#[test]
+fn phones_are_checked_by_their_digits() {
+    let config = ValidationConfig::default();
+    let phone_error = |phone: &str| {
+        errors(&config, &with(|person| person.phone = phone.to_string()))
+            .into_iter()
+            .map(|(_, message)| message)
+            .next()
+    };
+
+    assert_eq!(phone_error("555.010.0199"), None);
+    assert_eq!(
+        phone_error("555-01"),
+        Some("must have 7 to 15 digits".to_string())
+    );
+    assert_eq!(
+        phone_error("1234567890123456"),
+        Some("must have 7 to 15 digits".to_string())
+    );
+    assert_eq!(
+        phone_error("555-0100 ext 2"),
+        Some("may only contain digits, spaces, - . ( ) and a leading +".to_string())
+    );
+    assert_eq!(
+        phone_error("555+0100199"),
+        Some("may only contain digits, spaces, - . ( ) and a leading +".to_string())
+    );
+}
+
+#[test]
+fn states_must_be_accepted_codes_unless_any_is_allowed() {
+    let person = with(|person| person.state = "ZZ".to_string());
+
+    assert_eq!(
+        errors(&ValidationConfig::default(), &person),
+        [(
+            PersonField::State,
+            "is not an accepted state code".to_string()
+        )]
+    );
+    let any = ValidationConfig {
+        states: Vec::new(),
+        ..ValidationConfig::default()
+    };
+    assert!(errors(&any, &person).is_empty());
+}
+
+#[test]
+fn every_broken_field_is_reported_once_as_json() {
+    let person = with(|person| {
+        person.name.clear();
+        person.email = "not-an-email".to_string();
+        person.state = "Texas".to_string();
+    });
+    let errors = validation::validate(&ValidationConfig::default(), &person);
+    let response = validation::unprocessable(&errors);
+
+    assert_eq!(response.status, 422);
+    let body: Value = serde_json::from_slice(&response.body).unwrap();
+    assert_eq!(
+        body["errors"],
+        serde_json::json!([
+            {"field": "name", "message": "is required"},
+            {"field": "email", "message": "is not a valid email address"},
+            {"field": "state", "message": "must be at most 2 characters"},
+        ])
+    );
+}
+
+#[test]
+fn rules_that_cannot_be_met_are_config_issues() {
+    let config = ValidationConfig {
+        phone_min_digits: 10,
+        phone_max_digits: 7,
+        states: vec!["CA".to_string(), "CAL".to_string()],
+        max_lengths: MaxLengths {
+            name: 0,
+            city: 500,
+            ..MaxLengths::default()
+        },
+        ..ValidationConfig::default()
+    };
+    let mut issues = Vec::new();
+
+    config.validate(&mut issues);
+
+    let keys: Vec<&str> = issues.iter().map(|issue| issue.key.as_str()).collect();
+    assert_eq!(
+        keys,
+        [
+            "validation.max_lengths.name",
+            "validation.max_lengths.city",
+            "validation.phone_min_digits",
+            "validation.states[1]",
+        ]
+    );
+    assert_eq!(issues[1].message, "must be between 1 and 128");
+}